@@ -2,274 +2,6410 @@
 // programs/graph-arbitrage/src/lib.rs
 
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{
+    self, get_associated_token_address, get_associated_token_address_with_program_id, AssociatedToken,
+};
+use anchor_spl::{token, token_2022};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeAmount, BaseStateWithExtensions, StateWithExtensions},
+};
+use anchor_spl::token_interface::{
+    self, Approve, CloseAccount, Mint, Revoke, SyncNative, TokenAccount, TokenInterface, TransferChecked,
+};
 use anchor_lang::solana_program::{
+    compute_units::sol_remaining_compute_units,
+    hash::hash,
     instruction::{AccountMeta, Instruction},
-    program::invoke,
+    program::{invoke_signed, set_return_data},
+    pubkey,
     pubkey::Pubkey,
+    sysvar::instructions::{self, load_current_index_checked, load_instruction_at_checked},
 };
 
+mod math;
+
+use math::{checked_mul_div, checked_mul_div_rounded, constant_product_output, stable_swap_output, Rounding};
+use std::ops::Range;
+
 declare_id!("E3evReispCGYdx9XLp27u2BNBBrBEX8XfDjUhrNK9wwm");
 
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const DEX_REGISTRY_SEED: &[u8] = b"dex_registry";
+pub const EXECUTION_LOCK_SEED: &[u8] = b"execution_lock";
+pub const USER_STATS_SEED: &[u8] = b"user_stats";
+pub const ROUTE_CHECKPOINT_SEED: &[u8] = b"route_checkpoint";
+pub const MINT_ALLOWLIST_SEED: &[u8] = b"mint_allowlist";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const MAX_REGISTERED_DEXES: usize = 16;
+pub const MAX_DEX_BATCH: usize = 8;
+pub const MAX_ALLOWED_MINTS: usize = 32;
+pub const MAX_ADMIN_SIGNERS: usize = 5;
+pub const MAX_BATCH_STEPS: usize = 16;
+pub const DEFAULT_MIN_ROUTE_LEN: u8 = 2;
+pub const DEFAULT_MAX_ROUTE_LEN: u8 = 8;
+pub const DEFAULT_MIN_COMPUTE_PER_LEG: u64 = 40_000;
+pub const DEFAULT_ORACLE_MAX_DEVIATION_BPS: u16 = 100;
+pub const DEFAULT_ORACLE_MAX_AGE_SECS: u64 = 60;
+pub const DEFAULT_MAX_ORACLE_STALENESS_SLOTS: u64 = 25;
+pub const DEFAULT_MAX_ORACLE_CONFIDENCE_BPS: u16 = 50;
+pub const DEFAULT_MAX_ROUTE_DATA_LEN: u16 = 512;
+
+// msg! for progress logs that only help while debugging. Each formatted log
+// costs compute (pubkeys most, as they're base58-encoded: about 13.9k CU for
+// the per-leg "Step N: in -> out" line against about 740 for "projected
+// output"), so they're skipped unless config.verbose_logs is on, at the cost of
+// one branch. Error-path and dry-run logs always print.
+macro_rules! trace_msg {
+    ($config:expr, $($arg:tt)+) => {
+        if $config.verbose_logs {
+            msg!($($arg)+);
+        }
+    };
+}
+
 #[program]
 pub mod graph_arbitrage {
     use super::*;
 
     // 🎯 MAIN ARBITRAGE EXECUTION FUNCTION
-    pub fn execute_arbitrage_route(
-        ctx: Context<ExecuteArbitrageRoute>,
-        route: Vec<SwapStep>,
+    //
+    // remaining_accounts: one user-owned token account per step, holding that
+    // step's output_mint, in route order. Step 0 spends from user_token_account
+    // and every later step spends from the previous step's output account.
+    // After the token accounts come each step's DEX accounts, in route order
+    // (see dex_account_count for how many each DEX consumes), followed by the
+    // instructions sysvar when flash_loan_amount is nonzero. A flash loan is
+    // borrowed and repaid by the Solend instructions either side of this one.
+    //
+    // With wrap_sol, intermediate SOL legs may point at the user's (not yet
+    // existing) WSOL ATA: it is created for the route and closed back to SOL
+    // afterwards. Routes that start in SOL get max_input_amount lamports
+    // wrapped into user_token_account before the first leg.
+    //
+    // A step can also name its token accounts by remaining_accounts index:
+    // output_account replaces (and consumes no) positional token slot, and
+    // input_account spends from an account other than the previous output.
+    //
+    // Long routes that only fit through address lookup tables can set
+    // account_indices on a step instead: its DEX accounts are then picked from
+    // remaining_accounts by index, so pools and programs shared between legs
+    // are passed once, and the step consumes no positional DEX slots.
+    //
+    // The route must be a closed cycle: the last step's output_mint has to be
+    // the first step's input_mint, so profit is measured in the token we spent.
+    // The last step must also pay back into user_token_account itself.
+    //
+    // Passing the user's route_checkpoint opts into partial execution: with
+    // max_steps set, the run stops after that many legs and records its
+    // progress, and a later call with the same route resumes from there.
+    // Profit is only enforced on the call that finishes the route.
+    //
+    // Passing the user's vault trades from its token account instead of the
+    // user's ATA: user_token_account and every step's output account must then
+    // belong to the vault, which signs each swap through its PDA seeds.
+    //
+    // With direction = Reverse the route is walked backwards, each leg swapping
+    // output_mint for input_mint at the inverse rate. remaining_accounts then
+    // follow the order the legs actually execute in.
+    //
+    // With native_sol the route trades the user's lamports: token_mint must be
+    // the native mint and user_token_account the user's WSOL ATA. Up to
+    // max_input_amount lamports are wrapped into it before the first leg, SOL
+    // legs are wrapped as with wrap_sol, and once the run has settled the ATA
+    // is closed so everything in it, profit included, returns to the user as
+    // lamports. Profit is measured in lamports, which WSOL tracks one-to-one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_arbitrage_route<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+        route: Vec<VersionedSwapStep>,
         min_profit_bps: u16, // Minimum profit in basis points (100 = 1%)
         max_slippage_bps: u16, // Maximum acceptable slippage
+        deadline_slot: u64, // Last slot the quotes are valid for (u64::MAX = no expiry)
+        max_input_amount: u64, // Cap on how much of the balance the route may trade
+        flash_loan_amount: u64, // Borrowed by the preceding instruction and traded instead of the balance (0 = no loan)
+        jito_tip_lamports: u64, // Tip paid to jito_tip_account after a profitable run (0 = no tip)
+        wrap_sol: bool, // Wrap/unwrap native SOL for legs that touch it
+        min_profit_amount: u64, // Minimum profit in token units, on top of min_profit_bps (0 = off)
+        dry_run: bool, // Log each leg's CPI instead of invoking it; no funds move
+        close_temp_accounts: bool, // Close accounts created for this route, returning their rent
+        max_steps: u8, // Legs to run before checkpointing (0 = run to the end)
+        max_total_slippage_bps: u16, // Cap on slippage compounded across all legs (10000 = off)
+        direction: RouteDirection, // Reverse runs the cycle backwards
+        nonce: u64, // Must exceed the user's last nonce, so a retried transaction can't run twice
+        native_sol: bool, // Trade the user's lamports, unwrapping everything back to SOL at the end
+        fee_reserve_amount: u64, // Known transaction costs, in token units, profit must cover to count
     ) -> Result<()> {
-        msg!("🚀 Starting atomic arbitrage execution");
-        msg!("Route steps: {}", route.len());
-        msg!("Min profit: {}bps, Max slippage: {}bps", min_profit_bps, max_slippage_bps);
+        let compute_at_entry = sol_remaining_compute_units();
+        let route = orient_route(decode_route(&route)?, direction)?;
+        trace_msg!(ctx.accounts.config, "🚀 Starting atomic arbitrage execution");
+        trace_msg!(ctx.accounts.config, "Route steps: {}", route.len());
+        trace_msg!(ctx.accounts.config, "Min profit: {}bps, Max slippage: {}bps", min_profit_bps, max_slippage_bps);
 
         // 1. SAFETY CHECKS
-        require!(route.len() >= 3, ArbitrageError::RouteTooShort);
-        require!(route.len() <= 6, ArbitrageError::RouteTooLong);
+        require!(!ctx.accounts.config.paused, ArbitrageError::ProgramPaused);
+        require!(max_slippage_bps <= 10000, ArbitrageError::InvalidSlippage);
+        require!(max_total_slippage_bps <= 10000, ArbitrageError::InvalidSlippage);
+        let current_slot = Clock::get()?.slot;
+        require!(current_slot <= deadline_slot, ArbitrageError::DeadlineExceeded);
+        // A failed route reverts its own slot write, so this throttles successful runs
+        require!(
+            ctx.accounts.user_stats.cooldown_elapsed(current_slot, ctx.accounts.config.cooldown_slots),
+            ArbitrageError::CooldownActive
+        );
+        // Consumed even by runs that stop early (dry run, checkpoint); only a revert gives it back
+        ctx.accounts.user_stats.consume_nonce(nonce)?;
+        validate_route_length(&route, &ctx.accounts.config)?;
         require!(min_profit_bps > 0, ArbitrageError::InvalidMinProfit);
+        validate_route_continuity(&route, ctx.accounts.token_mint.key())?;
+        validate_route_cycle(&route)?;
+        validate_no_repeated_mints(&route, &ctx.accounts.config)?;
+        validate_distinct_dexes(&route, &ctx.accounts.config)?;
+        validate_step_parameters(&route)?;
+        validate_route_data_size(&route, &ctx.accounts.config)?;
+        validate_route_mints(&route, &ctx.accounts.mint_allowlist)?;
+        // Before anything is wrapped, created or borrowed, make sure every leg's accounts are there
+        let loan_accounts = if flash_loan_amount > 0 { FLASH_LOAN_ACCOUNT_COUNT } else { 0 };
+        require!(
+            positional_account_count(&route, ctx.remaining_accounts.len())? + loan_accounts
+                <= ctx.remaining_accounts.len(),
+            ArbitrageError::MissingAccounts
+        );
+        // A SOL-denominated route wraps whatever SOL legs it touches
+        let wrap_sol = wrap_sol || native_sol;
+        if native_sol {
+            require_keys_eq!(ctx.accounts.token_mint.key(), NATIVE_MINT, ArbitrageError::MintMismatch);
+        }
+        // Wrapping moves lamports, which a dry run must never do
+        require!(!(dry_run && wrap_sol), ArbitrageError::DryRunUnsupported);
+        // The vault PDA can't pay rent for WSOL accounts or sign the lender's repay
+        require!(
+            ctx.accounts.vault.is_none() || !(wrap_sol || flash_loan_amount > 0),
+            ArbitrageError::VaultUnsupported
+        );
 
-        // 2. RECORD STARTING BALANCE
-        let start_balance = ctx.accounts.user_token_account.amount;
-        msg!("Starting balance: {}", start_balance);
+        // 2. TAKE THE EXECUTION LOCK
+        // Persisted immediately so a DEX that re-enters through CPI sees it.
+        // Any error below reverts the whole transaction, lock write included,
+        // so a failed run can never leave the lock stuck.
+        require!(!ctx.accounts.execution_lock.locked, ArbitrageError::ReentrancyDetected);
+        ctx.accounts.execution_lock.locked = true;
+        ctx.accounts.execution_lock.bump = ctx.bumps.execution_lock;
+        ctx.accounts.execution_lock.exit(&crate::ID)?;
 
-        // 3. EXECUTE SWAP SEQUENCE ATOMICALLY
-        let mut current_amount = start_balance;
-        
-        for (step_index, step) in route.iter().enumerate() {
-            msg!("Step {}: {} -> {}", step_index + 1, step.input_mint, step.output_mint);
-            
-            // Execute individual swap through Jupiter/DEX
-            let swap_result = execute_single_swap(
-                &ctx,
-                step,
-                current_amount,
-                max_slippage_bps,
-            )?;
-            
-            current_amount = swap_result.output_amount;
-            msg!("Step {} output: {}", step_index + 1, current_amount);
-            
-            // SAFETY: If any step fails, entire transaction reverts
-            require!(swap_result.success, ArbitrageError::SwapFailed);
+        // 3. LOAD ANY CHECKPOINTED PROGRESS
+        let checkpointing = ctx.accounts.route_checkpoint.is_some();
+        require!(max_steps == 0 || checkpointing, ArbitrageError::MissingCheckpoint);
+        // A flash loan has to be repaid in the transaction that took it
+        require!(!(checkpointing && flash_loan_amount > 0), ArbitrageError::CheckpointUnsupported);
+        // Unwrapping at the end of a partial run would leave nothing to resume from
+        require!(!(checkpointing && native_sol), ArbitrageError::CheckpointUnsupported);
+        let route_hash = hash_route(&route)?;
+        let resume = match &ctx.accounts.route_checkpoint {
+            Some(checkpoint) if checkpoint.active => {
+                require!(checkpoint.route_hash == route_hash, ArbitrageError::CheckpointRouteMismatch);
+                trace_msg!(ctx.accounts.config, "Resuming at step {} with {}", checkpoint.next_step + 1, checkpoint.current_amount);
+                Some(RouteProgress {
+                    next_step: checkpoint.next_step as usize,
+                    current_amount: checkpoint.current_amount,
+                    spent: checkpoint.spent,
+                    trade_amount: checkpoint.trade_amount,
+                })
+            }
+            _ => None,
+        };
+
+        // 4. PREPARE WRAPPED SOL
+        let temp_wsol_accounts = if wrap_sol {
+            let temp_wsol_accounts = create_temp_wsol_accounts(&ctx, &route)?;
+            if ctx.accounts.token_mint.key() == NATIVE_MINT && resume.is_none() {
+                // Trading lamports, leave the user enough to pay the tip afterwards
+                let target = if native_sol {
+                    let spendable = ctx.accounts.user.lamports().saturating_sub(jito_tip_lamports);
+                    max_input_amount.min(ctx.accounts.user_token_account.amount.saturating_add(spendable))
+                } else {
+                    max_input_amount
+                };
+                wrap_starting_sol(&ctx, target)?;
+                ctx.accounts.user_token_account.reload()?;
+            }
+            temp_wsol_accounts
+        } else {
+            Vec::new()
+        };
+
+        // 5. RECORD STARTING BALANCE
+        // A flash loan has already landed, so the balance includes it until the repay.
+        // A resumed run starts from this call's balance plus what earlier calls took
+        // out of it, so tokens deposited in between never count towards profit.
+        let use_flash_loan = flash_loan_amount > 0;
+        let call_start_balance = ctx.accounts.user_token_account.amount;
+        let progress = match resume {
+            Some(progress) => progress,
+            None => {
+                trace_msg!(ctx.accounts.config, "Starting balance: {}", call_start_balance);
+                let trade_amount = starting_trade_amount(call_start_balance, max_input_amount, flash_loan_amount)?;
+                trace_msg!(ctx.accounts.config, "Trading amount: {}", trade_amount);
+
+                RouteProgress { next_step: 0, current_amount: trade_amount, spent: 0, trade_amount }
+            }
+        };
+        let start_balance = call_start_balance.saturating_add(progress.spent);
+        let trade_amount = progress.trade_amount;
+        let end_step = match max_steps {
+            0 => route.len(),
+            max_steps => (progress.next_step + max_steps as usize).min(route.len()),
+        };
+
+        // Worst-case external calls this run could make, remainder venues included
+        let max_cpis = ctx.accounts.config.max_cpis;
+        if max_cpis > 0 {
+            let swap_cpis: usize = route[progress.next_step..end_step].iter().map(planned_swap_cpis).sum();
+            require!(swap_cpis <= max_cpis as usize, ArbitrageError::TooManyCpis);
+        }
+
+        // 6. RESOLVE PER-STEP TOKEN ACCOUNTS
+        let route_accounts = resolve_route_accounts(&ctx, &route, ctx.remaining_accounts)?;
+        let intermediate_before = intermediate_balances(&ctx, &route, &route_accounts)?;
+
+        // 7. CHECK THE FLASH LOAN BRACKETING THIS INSTRUCTION
+        if use_flash_loan && !dry_run {
+            let flash_accounts = route_accounts
+                .tail
+                .get(..FLASH_LOAN_ACCOUNT_COUNT)
+                .ok_or(ArbitrageError::MissingFlashLoanAccounts)?;
+            verify_flash_loan(&ctx, flash_accounts, flash_loan_amount)?;
+        }
+
+        // 8. EXECUTE SWAP SEQUENCE ATOMICALLY
+        // What the quotes promised, so an abort can say how far reality fell short
+        let expected_profit_bps = project_route_profit_bps(&route, trade_amount, &ctx.accounts.config)?;
+        let current_amount = execute_route_steps(
+            &ctx,
+            &route,
+            &route_accounts,
+            progress.next_step..end_step,
+            progress.current_amount,
+            trade_amount,
+            max_slippage_bps,
+            max_total_slippage_bps,
+            expected_profit_bps,
+            dry_run,
+        )?;
+
+        // 9. STOP HERE ON A DRY RUN: nothing moved, so there is no profit to enforce
+        if dry_run {
+            msg!("[dry run] Projected final amount: {} from {}", current_amount, trade_amount);
+            ctx.accounts.execution_lock.locked = false;
+            return Ok(());
+        }
+
+        // 10. CHECKPOINT A PARTIAL RUN, OR RETIRE THE CHECKPOINT OF A FINISHED ONE
+        if let Some(checkpoint) = ctx.accounts.route_checkpoint.as_mut() {
+            if end_step < route.len() {
+                checkpoint.active = true;
+                checkpoint.route_hash = route_hash;
+                checkpoint.next_step = end_step as u8;
+                checkpoint.current_amount = current_amount;
+                // Net of anything this call paid back, what the route has taken out so far
+                let balance = load_token_account(ctx.accounts.user_token_account.as_ref())?.amount;
+                checkpoint.spent = start_balance.saturating_sub(balance);
+                checkpoint.trade_amount = trade_amount;
+                msg!("⏸️ Checkpointed after step {} of {}", end_step, route.len());
+
+                ctx.accounts.execution_lock.locked = false;
+                return Ok(());
+            }
+            checkpoint.active = false;
         }
 
-        // 4. PROFIT VALIDATION
+        // 11. NOTHING MAY BE LEFT STRANDED IN AN INTERMEDIATE TOKEN
+        require_no_residual_tokens(&intermediate_before)?;
+
+        // 12. THE PROCEEDS MUST COVER THE LOAN
+        // The repay instruction after this one pulls the loan back out of
+        // user_token_account, so the route has to have returned at least it
+        if use_flash_loan {
+            let balance = load_token_account(ctx.accounts.user_token_account.as_ref())?.amount;
+            require!(balance >= start_balance, ArbitrageError::FlashRepayFailed);
+        }
+
+        // 13. PROFIT VALIDATION
+        // The route closes into user_token_account; re-read it now that every CPI
+        // (transfer hooks included) has settled, so neither the profit check nor
+        // the transfers below work from the balance deserialized on entry
+        ctx.accounts.user_token_account.reload()?;
         let final_balance = ctx.accounts.user_token_account.amount;
-        let profit = final_balance.saturating_sub(start_balance);
-        let profit_bps = (profit * 10000) / start_balance;
+        let gross_profit = final_balance.saturating_sub(start_balance);
+
+        // The protocol takes its cut first, then the strategist; the user keeps the rest
+        let protocol_fee = calculate_bps_share(gross_profit, ctx.accounts.config.fee_bps)?;
+        let strategist_fee = calculate_bps_share(gross_profit - protocol_fee, ctx.accounts.config.strategist_bps)?;
+        let profit = gross_profit - protocol_fee - strategist_fee;
+        let profit_bps = calculate_profit_bps(profit, trade_amount)?;
         
-        msg!("Final balance: {}, Profit: {} ({}bps)", final_balance, profit, profit_bps);
+        trace_msg!(
+            ctx.accounts.config,
+            "Final balance: {}, Profit: {} ({}bps), Protocol fee: {}, Strategist fee: {}",
+            final_balance, profit, profit_bps, protocol_fee, strategist_fee
+        );
 
-        // 5. ENSURE MINIMUM PROFIT ACHIEVED
-        require!(profit_bps >= min_profit_bps as u64, ArbitrageError::InsufficientProfit);
+        // 14. ENSURE MINIMUM PROFIT ACHIEVED (net of fees and the caller's fee reserve)
+        // The reserve only raises the bar; the profit reported and paid out is unchanged
+        let net_profit = profit.saturating_sub(fee_reserve_amount);
+        let net_profit_bps = calculate_profit_bps(net_profit, trade_amount)?;
+        if net_profit_bps < min_profit_bps as u64 || net_profit < min_profit_amount {
+            // Quoted above the bar but realized below it: the fills slipped it away
+            let slipped = expected_profit_bps >= min_profit_bps as u64;
+            emit!(ArbitrageAborted {
+                reason: if slipped { AbortReason::ProfitLostToSlippage } else { AbortReason::InsufficientProfit },
+                realized_profit_bps: net_profit_bps,
+                expected_profit_bps,
+            });
+            return if slipped {
+                err!(ArbitrageError::ProfitLostToSlippage)
+            } else {
+                err!(ArbitrageError::InsufficientProfit)
+            };
+        }
+
+        // 15. COLLECT PROTOCOL FEE
+        collect_protocol_fee(&ctx, protocol_fee)?;
+        pay_strategist(&ctx, strategist_fee)?;
+
+        // 16. PAY OUT PROFIT TO THE RECIPIENT, IF ONE WAS GIVEN
+        pay_profit_recipient(&ctx, profit)?;
+
+        // 17. TIP THE BLOCK ENGINE
+        pay_jito_tip(&ctx, jito_tip_lamports)?;
+
+        // 18. CLOSE TEMPORARY ACCOUNTS, UNWRAPPING WSOL AND RECLAIMING RENT
+        // Left open they can be reused by the next route. On the error path
+        // there is nothing to clean up: the revert undoes their creation, rent included.
+        let mut accounts_to_close = if close_temp_accounts { temp_wsol_accounts } else { Vec::new() };
+        // A SOL-denominated route always unwraps its base account back to lamports
+        if native_sol {
+            accounts_to_close.push(ctx.accounts.user_token_account.as_ref());
+        }
+        for wsol_account in accounts_to_close {
+            token_interface::close_account(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                CloseAccount {
+                    account: wsol_account.clone(),
+                    destination: ctx.accounts.user.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ))?;
+        }
+        if native_sol {
+            trace_msg!(ctx.accounts.config, "Unwrapped to {} lamports", ctx.accounts.user.lamports());
+        }
 
-        // 6. SUCCESS! Log the profitable arbitrage
+        // 19. SUCCESS! Log the profitable arbitrage
         emit!(ArbitrageExecuted {
             user: ctx.accounts.user.key(),
             start_amount: start_balance,
-            final_amount: final_balance,
-            profit: profit,
-            profit_bps: profit_bps,
+            trade_amount,
+            final_amount: final_balance - protocol_fee - strategist_fee,
+            profit,
+            profit_bps,
+            protocol_fee,
+            strategist_fee,
             steps: route.len() as u8,
+            route_hash,
+            compute_used: compute_used_since(compute_at_entry),
+            profit_usd: profit_in_usd(&ctx, profit),
         });
 
-        msg!("✅ Arbitrage completed successfully! Profit: {}bps", profit_bps);
+        // 20. RETURN THE OUTCOME TO COMPOSING CALLERS
+        let outcome = ArbitrageOutcome {
+            profit,
+            profit_bps,
+            steps: route.len() as u8,
+        };
+        let mut return_data = Vec::new();
+        outcome.serialize(&mut return_data)?;
+        set_return_data(&return_data);
+
+        // 21. RECORD LIFETIME STATS
+        let stats = &mut ctx.accounts.user_stats;
+        stats.bump = ctx.bumps.user_stats;
+        stats.record(profit, profit_bps, current_slot);
+
+        // 22. RELEASE THE EXECUTION LOCK
+        ctx.accounts.execution_lock.locked = false;
+
+        trace_msg!(ctx.accounts.config, "✅ Arbitrage completed successfully! Profit: {}bps", profit_bps);
         Ok(())
     }
 
-    // 🔄 EMERGENCY FUNCTION: Cancel if something goes wrong
-    pub fn emergency_cancel(ctx: Context<EmergencyCancel>) -> Result<()> {
-        msg!("🚨 Emergency cancel triggered - all funds safe");
-        // Contract automatically reverts - no action needed
-        // This function exists for explicit cancellation
+    // 📦 BATCHED ROUTES: several independent cycles sharing one tip
+    //
+    // Every route starts and ends in user_token_account and trades up to
+    // max_input_amount of the balance it finds. remaining_accounts hold each
+    // route's token and DEX accounts laid out as for a single route, one route
+    // after another. A route whose quotes don't clear min_profit_bps is skipped;
+    // one that runs is bound like any route, so a failed leg reverts the batch.
+    // The profit bounds, protocol fee and tip apply to the batch as a whole.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_arbitrage_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+        routes: Vec<Vec<VersionedSwapStep>>,
+        min_profit_bps: u16, // Bar each route's quote must clear, and the batch's realized profit
+        max_slippage_bps: u16,
+        deadline_slot: u64,
+        max_input_amount: u64, // Per route
+        jito_tip_lamports: u64,
+        min_profit_amount: u64, // Across the batch, net of protocol fee (0 = off)
+        nonce: u64,
+        fee_reserve_amount: u64, // Once for the whole batch
+    ) -> Result<()> {
+        let routes = routes.iter().map(|route| decode_route(route)).collect::<Result<Vec<_>>>()?;
+        trace_msg!(ctx.accounts.config, "📦 Starting batch of {} routes", routes.len());
+
+        // 1. SAFETY CHECKS
+        require!(!ctx.accounts.config.paused, ArbitrageError::ProgramPaused);
+        require!(max_slippage_bps <= 10000, ArbitrageError::InvalidSlippage);
+        let current_slot = Clock::get()?.slot;
+        require!(current_slot <= deadline_slot, ArbitrageError::DeadlineExceeded);
+        require!(
+            ctx.accounts.user_stats.cooldown_elapsed(current_slot, ctx.accounts.config.cooldown_slots),
+            ArbitrageError::CooldownActive
+        );
+        // Consumed even by runs that stop early (dry run, checkpoint); only a revert gives it back
+        ctx.accounts.user_stats.consume_nonce(nonce)?;
+        require!(min_profit_bps > 0, ArbitrageError::InvalidMinProfit);
+        require!(!routes.is_empty(), ArbitrageError::EmptyBatch);
+        let total_steps: usize = routes.iter().map(Vec::len).sum();
+        require!(total_steps <= MAX_BATCH_STEPS, ArbitrageError::BatchTooLarge);
+        let mut required_accounts = 0;
+        for route in &routes {
+            validate_route_length(route, &ctx.accounts.config)?;
+            validate_route_continuity(route, ctx.accounts.token_mint.key())?;
+            validate_route_cycle(route)?;
+            validate_no_repeated_mints(route, &ctx.accounts.config)?;
+            validate_distinct_dexes(route, &ctx.accounts.config)?;
+            validate_step_parameters(route)?;
+            validate_route_data_size(route, &ctx.accounts.config)?;
+            validate_route_mints(route, &ctx.accounts.mint_allowlist)?;
+            required_accounts += positional_account_count(route, ctx.remaining_accounts.len())?;
+        }
+        require!(required_accounts <= ctx.remaining_accounts.len(), ArbitrageError::MissingAccounts);
+
+        // 2. TAKE THE EXECUTION LOCK
+        require!(!ctx.accounts.execution_lock.locked, ArbitrageError::ReentrancyDetected);
+        ctx.accounts.execution_lock.locked = true;
+        ctx.accounts.execution_lock.bump = ctx.bumps.execution_lock;
+        ctx.accounts.execution_lock.exit(&crate::ID)?;
+
+        // 3. RESOLVE EACH ROUTE'S ACCOUNTS, ONE ROUTE AFTER ANOTHER
+        // Skipped routes still occupy their slots, so the layout doesn't depend on prices
+        let mut remaining: &'info [AccountInfo<'info>] = ctx.remaining_accounts;
+        let mut batch_accounts = Vec::with_capacity(routes.len());
+        for route in &routes {
+            let route_accounts = resolve_route_accounts(&ctx, route, remaining)?;
+            remaining = route_accounts.tail;
+            batch_accounts.push(route_accounts);
+        }
+
+        let max_cpis = ctx.accounts.config.max_cpis;
+        if max_cpis > 0 {
+            let swap_cpis: usize = routes.iter().flatten().map(planned_swap_cpis).sum();
+            require!(swap_cpis <= max_cpis as usize, ArbitrageError::TooManyCpis);
+        }
+
+        // 4. RUN EVERY ROUTE WHOSE QUOTES CLEAR THE BAR
+        let mut total_traded: u64 = 0;
+        let mut total_profit: u64 = 0;
+        let mut total_fee: u64 = 0;
+        let mut total_strategist_fee: u64 = 0;
+        let mut routes_executed: u8 = 0;
+        for (route_index, (route, route_accounts)) in routes.iter().zip(&batch_accounts).enumerate() {
+            let compute_at_start = sol_remaining_compute_units();
+            let start_balance = load_token_account(ctx.accounts.user_token_account.as_ref())?.amount;
+            let trade_amount = start_balance.min(max_input_amount);
+            require!(trade_amount > 0, ArbitrageError::ZeroStartBalance);
+
+            let expected_profit_bps = project_route_profit_bps(route, trade_amount, &ctx.accounts.config)?;
+            if expected_profit_bps < min_profit_bps as u64 {
+                trace_msg!(ctx.accounts.config, "Route {} skipped: quoted {}bps", route_index + 1, expected_profit_bps);
+                emit!(RouteSkipped { route_index: route_index as u8, expected_profit_bps });
+                continue;
+            }
+
+            trace_msg!(ctx.accounts.config, "Route {}: trading {}", route_index + 1, trade_amount);
+            let intermediate_before = intermediate_balances(&ctx, route, route_accounts)?;
+            execute_route_steps(
+                &ctx,
+                route,
+                route_accounts,
+                0..route.len(),
+                trade_amount,
+                trade_amount,
+                max_slippage_bps,
+                10000,
+                expected_profit_bps,
+                false,
+            )?;
+            require_no_residual_tokens(&intermediate_before)?;
+
+            let final_balance = load_token_account(ctx.accounts.user_token_account.as_ref())?.amount;
+            let gross_profit = final_balance.saturating_sub(start_balance);
+            let protocol_fee = calculate_bps_share(gross_profit, ctx.accounts.config.fee_bps)?;
+            let strategist_fee = calculate_bps_share(gross_profit - protocol_fee, ctx.accounts.config.strategist_bps)?;
+            let profit = gross_profit - protocol_fee - strategist_fee;
+            emit!(ArbitrageExecuted {
+                user: ctx.accounts.user.key(),
+                start_amount: start_balance,
+                trade_amount,
+                final_amount: final_balance - protocol_fee - strategist_fee,
+                profit,
+                profit_bps: calculate_profit_bps(profit, trade_amount)?,
+                protocol_fee,
+                strategist_fee,
+                steps: route.len() as u8,
+                route_hash: hash_route(route)?,
+                compute_used: compute_used_since(compute_at_start),
+                profit_usd: profit_in_usd(&ctx, profit),
+            });
+
+            total_traded = total_traded.checked_add(trade_amount).ok_or(ArbitrageError::MathOverflow)?;
+            total_profit = total_profit.checked_add(profit).ok_or(ArbitrageError::MathOverflow)?;
+            total_fee = total_fee.checked_add(protocol_fee).ok_or(ArbitrageError::MathOverflow)?;
+            total_strategist_fee = total_strategist_fee.checked_add(strategist_fee).ok_or(ArbitrageError::MathOverflow)?;
+            routes_executed += 1;
+        }
+
+        // 5. ENSURE THE BATCH AS A WHOLE WAS WORTH IT (net of fees and the caller's fee reserve)
+        require!(routes_executed > 0, ArbitrageError::InsufficientProfit);
+        let profit_bps = calculate_profit_bps(total_profit, total_traded)?;
+        trace_msg!(ctx.accounts.config, "Batch profit: {} ({}bps), Protocol fee: {}", total_profit, profit_bps, total_fee);
+        let net_profit = total_profit.saturating_sub(fee_reserve_amount);
+        require!(
+            calculate_profit_bps(net_profit, total_traded)? >= min_profit_bps as u64,
+            ArbitrageError::InsufficientProfit
+        );
+        require!(net_profit >= min_profit_amount, ArbitrageError::InsufficientProfit);
+
+        // 6. SETTLE: PROTOCOL FEE, STRATEGIST SHARE, RECIPIENT PAYOUT, ONE TIP FOR THE WHOLE BATCH
+        collect_protocol_fee(&ctx, total_fee)?;
+        pay_strategist(&ctx, total_strategist_fee)?;
+        pay_profit_recipient(&ctx, total_profit)?;
+        pay_jito_tip(&ctx, jito_tip_lamports)?;
+
+        // 7. RETURN THE OUTCOME TO COMPOSING CALLERS
+        let outcome = BatchOutcome {
+            profit: total_profit,
+            profit_bps,
+            routes_executed,
+            routes_skipped: routes.len() as u8 - routes_executed,
+        };
+        let mut return_data = Vec::new();
+        outcome.serialize(&mut return_data)?;
+        set_return_data(&return_data);
+
+        // 8. RECORD LIFETIME STATS
+        let stats = &mut ctx.accounts.user_stats;
+        stats.bump = ctx.bumps.user_stats;
+        stats.record(total_profit, profit_bps, current_slot);
+
+        // 9. RELEASE THE EXECUTION LOCK
+        ctx.accounts.execution_lock.locked = false;
+
+        trace_msg!(ctx.accounts.config, "✅ Batch completed: {} of {} routes ran, {}bps", routes_executed, routes.len(), profit_bps);
         Ok(())
     }
-}
 
-// 🏗️ ATOMIC SWAP EXECUTION HELPER
-fn execute_single_swap(
-    ctx: &Context<ExecuteArbitrageRoute>,
-    step: &SwapStep,
-    input_amount: u64,
-    max_slippage_bps: u16,
-) -> Result<SwapResult> {
-    msg!("Executing swap: {} -> {}", step.input_mint, step.output_mint);
+    // 🔮 SIMULATION: project a route's profit from expected_rate alone
+    //
+    // No CPIs are made; a borsh NetProfitPreview is written as return data.
+    // The tip and transaction fee are costs the route must cover, given in
+    // token_mint units so they net off directly (convert lamports off-chain).
+    pub fn simulate_arbitrage(
+        ctx: Context<SimulateArbitrage>,
+        route: Vec<VersionedSwapStep>,
+        input_amount: u64,
+        tip_amount: u64,
+        tx_fee_amount: u64,
+    ) -> Result<()> {
+        let route = decode_route(&route)?;
+        msg!("🔮 Simulating arbitrage route ({} steps)", route.len());
 
-    // Calculate minimum acceptable output (accounting for slippage)
-    let min_output = (input_amount * step.expected_rate * (10000 - max_slippage_bps as u64)) / 10000000;
-    
-    match step.dex {
-        DexType::Jupiter => execute_jupiter_swap(ctx, step, input_amount, min_output),
-        DexType::Raydium => execute_raydium_swap(ctx, step, input_amount, min_output),
-        DexType::Orca => execute_orca_swap(ctx, step, input_amount, min_output),
+        validate_route_length(&route, &ctx.accounts.config)?;
+        validate_route_continuity(&route, ctx.accounts.token_mint.key())?;
+        validate_route_cycle(&route)?;
+        require!(input_amount > 0, ArbitrageError::ZeroStartBalance);
+
+        let gross = project_route_output(&route, input_amount, &ctx.accounts.config)?.saturating_sub(input_amount);
+        let preview = NetProfitPreview::new(gross, &ctx.accounts.config, tip_amount, tx_fee_amount)?;
+        msg!(
+            "Projected profit: {} gross, {} fees, {} tip, {} net",
+            preview.gross, preview.fees, preview.tip, preview.net
+        );
+
+        let mut return_data = Vec::new();
+        preview.serialize(&mut return_data)?;
+        set_return_data(&return_data);
+        Ok(())
     }
-}
 
-// 🪐 JUPITER INTEGRATION
-fn execute_jupiter_swap(
-    ctx: &Context<ExecuteArbitrageRoute>,
-    step: &SwapStep,
-    input_amount: u64,
-    min_output: u64,
-) -> Result<SwapResult> {
-    msg!("Executing Jupiter swap");
-    
-    // Create Jupiter swap instruction
-    let jupiter_instruction = Instruction {
-        program_id: step.program_id,
-        accounts: vec![
-            AccountMeta::new(ctx.accounts.user_token_account.key(), false),
-            AccountMeta::new(step.input_mint, false),
-            AccountMeta::new(step.output_mint, false),
-            AccountMeta::new_readonly(ctx.accounts.user.key(), true),
-        ],
-        data: create_jupiter_swap_data(input_amount, min_output, step.route_data.clone()),
-    };
+    // 🕰️ BACKTESTING: replay a route against recorded pool state
+    //
+    // snapshots holds one recorded pool state per step, in route order. Each
+    // leg is priced off its snapshot alone (constant product, or the stable-swap
+    // invariant when amp is set) after the snapshot's fee, so the result is
+    // deterministic and no CPI is made. The per-leg outputs are written as
+    // borsh Vec<u64> return data.
+    pub fn simulate_against_snapshot(
+        ctx: Context<SimulateArbitrage>,
+        route: Vec<VersionedSwapStep>,
+        snapshots: Vec<PoolStateSnapshot>,
+        input_amount: u64,
+    ) -> Result<()> {
+        let route = decode_route(&route)?;
+        msg!("🕰️ Replaying arbitrage route ({} steps) against snapshots", route.len());
 
-    // Execute the swap through CPI
-    invoke(
-        &jupiter_instruction,
-        &[
-            ctx.accounts.user_token_account.to_account_info(),
-            ctx.accounts.user.to_account_info(),
-        ],
-    )?;
+        validate_route_length(&route, &ctx.accounts.config)?;
+        validate_route_continuity(&route, ctx.accounts.token_mint.key())?;
+        validate_route_cycle(&route)?;
+        require!(snapshots.len() == route.len(), ArbitrageError::SnapshotMismatch);
+        require!(input_amount > 0, ArbitrageError::ZeroStartBalance);
 
-    // Verify swap success by checking balance change
-    let new_balance = ctx.accounts.user_token_account.amount;
-    let output_amount = new_balance; // Simplified - real implementation would track per-token
+        let leg_outputs = replay_leg_outputs(&snapshots, input_amount)?;
+        let final_amount = leg_outputs.last().copied().unwrap_or(input_amount);
+        msg!("Replayed profit: {}", final_amount as i128 - input_amount as i128);
 
-    Ok(SwapResult {
-        success: output_amount >= min_output,
-        output_amount,
-        slippage_bps: calculate_slippage(input_amount * step.expected_rate / 1000, output_amount),
-    })
-}
+        let mut return_data = Vec::new();
+        leg_outputs.serialize(&mut return_data)?;
+        set_return_data(&return_data);
+        Ok(())
+    }
 
-// 🌊 RAYDIUM INTEGRATION (similar pattern)
-fn execute_raydium_swap(
-    ctx: &Context<ExecuteArbitrageRoute>,
-    step: &SwapStep,
-    input_amount: u64,
-    min_output: u64,
-) -> Result<SwapResult> {
-    msg!("Executing Raydium swap");
-    // Implementation similar to Jupiter but with Raydium-specific logic
-    Ok(SwapResult {
-        success: true,
-        output_amount: input_amount * step.expected_rate / 1000, // Simplified
-        slippage_bps: 0,
-    })
-}
+    // ⚙️ PROGRAM CONFIG: operator controls
+    //
+    // Admin instructions are approved by `threshold` of `signers`, who sign the
+    // transaction and are passed as remaining_accounts. Only the program's
+    // upgrade authority may create the config, so nobody can front-run the
+    // deployment and install themselves as admins.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        validate_admin_signers(&signers, threshold)?;
 
-// 🐋 ORCA INTEGRATION (similar pattern)
-fn execute_orca_swap(
-    ctx: &Context<ExecuteArbitrageRoute>,
-    step: &SwapStep,
-    input_amount: u64,
-    min_output: u64,
-) -> Result<SwapResult> {
-    msg!("Executing Orca swap");
-    // Implementation similar to Jupiter but with Orca-specific logic
-    Ok(SwapResult {
-        success: true,
-        output_amount: input_amount * step.expected_rate / 1000, // Simplified
-        slippage_bps: 0,
-    })
-}
+        let config = &mut ctx.accounts.config;
+        config.signers = signers;
+        config.threshold = threshold;
+        config.paused = false;
+        config.fee_bps = 0;
+        config.min_route_len = DEFAULT_MIN_ROUTE_LEN;
+        config.max_route_len = DEFAULT_MAX_ROUTE_LEN;
+        config.min_compute_per_leg = DEFAULT_MIN_COMPUTE_PER_LEG;
+        config.cooldown_slots = 0;
+        config.max_cpis = 0;
+        config.oracle_max_deviation_bps = DEFAULT_ORACLE_MAX_DEVIATION_BPS;
+        config.oracle_max_age_secs = DEFAULT_ORACLE_MAX_AGE_SECS;
+        config.max_oracle_staleness_slots = DEFAULT_MAX_ORACLE_STALENESS_SLOTS;
+        config.verbose_logs = false; // Progress logs cost compute; set_verbose_logs turns them on
+        config.check_residual_tokens = true;
+        config.pool_snapshots = false;
+        config.strategist = None;
+        config.strategist_bps = 0;
+        config.allow_self_sandwich = false;
+        config.max_route_data_len = DEFAULT_MAX_ROUTE_DATA_LEN;
+        config.meter_legs = false;
+        config.allow_repeated_mints = false;
+        config.breaker_reporter = None;
+        config.breaker_threshold = 0;
+        config.consecutive_failures = 0;
+        config.max_oracle_confidence_bps = DEFAULT_MAX_ORACLE_CONFIDENCE_BPS;
+        config.pending_signers = Vec::new();
+        config.pending_threshold = 0;
+        config.max_distinct_dexes = 0;
+        config.bump = ctx.bumps.config;
 
-// 📊 HELPER FUNCTIONS
-fn create_jupiter_swap_data(input_amount: u64, min_output: u64, route_data: Vec<u8>) -> Vec<u8> {
-    // Create Jupiter-compatible instruction data
-    let mut data = Vec::new();
-    data.extend_from_slice(&input_amount.to_le_bytes());
-    data.extend_from_slice(&min_output.to_le_bytes());
-    data.extend_from_slice(&route_data);
-    data
-}
+        msg!("⚙️ Program config initialized, {}-of-{} admin signers", config.threshold, config.signers.len());
+        Ok(())
+    }
 
-fn calculate_slippage(expected: u64, actual: u64) -> u16 {
-    if expected == 0 { return 0; }
-    let diff = expected.saturating_sub(actual);
-    ((diff * 10000) / expected) as u16
-}
+    // 🔑 AUTHORITY TRANSFER: replace the admin signer set in two steps
+    //
+    // The current admins propose a new set; it takes effect only once every
+    // proposed signer accepts. A mistyped key can't sign, so it can never
+    // become part of the config and lock the admins out. Proposing again
+    // replaces the pending set, and an empty proposal withdraws it.
+    pub fn propose_authority(ctx: Context<UpdateConfig>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        if !signers.is_empty() {
+            validate_admin_signers(&signers, threshold)?;
+        }
 
-// 🏗️ ACCOUNT STRUCTURES
-#[derive(Accounts)]
-pub struct ExecuteArbitrageRoute<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    
-    #[account(
-        mut,
-        associated_token::mint = token_mint,
-        associated_token::authority = user,
-    )]
-    pub user_token_account: Account<'info, TokenAccount>,
-    
-    pub token_mint: Account<'info, token::Mint>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
+        let config = &mut ctx.accounts.config;
+        config.pending_signers = signers;
+        config.pending_threshold = threshold;
+        emit!(AuthorityProposed {
+            signers: config.pending_signers.clone(),
+            threshold: config.pending_threshold,
+        });
+        msg!("🔑 Proposed {}-of-{} admin signers", threshold, config.pending_signers.len());
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct EmergencyCancel<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-}
+    // Every proposed signer signs and is passed as remaining_accounts
+    pub fn accept_authority(ctx: Context<UpdateConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(!config.pending_signers.is_empty(), ArbitrageError::NoPendingAuthority);
+        let accepted = config.pending_signers.iter().all(|signer| {
+            ctx.remaining_accounts
+                .iter()
+                .any(|account| account.is_signer && account.key == signer)
+        });
+        require!(accepted, ArbitrageError::Unauthorized);
 
-// 📋 DATA STRUCTURES
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub struct SwapStep {
-    pub input_mint: Pubkey,
-    pub output_mint: Pubkey,
-    pub dex: DexType,
-    pub program_id: Pubkey,
-    pub expected_rate: u64, // Rate * 1000 for precision
-    pub route_data: Vec<u8>, // DEX-specific routing data
-}
+        config.signers = std::mem::take(&mut config.pending_signers);
+        config.threshold = config.pending_threshold;
+        config.pending_threshold = 0;
+        emit!(AuthorityAccepted {
+            signers: config.signers.clone(),
+            threshold: config.threshold,
+        });
+        msg!("🔑 Admin signers now {}-of-{}", config.threshold, config.signers.len());
+        Ok(())
+    }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub enum DexType {
-    Jupiter,
-    Raydium,
-    Orca,
-}
+    // 🛑 KILL SWITCH: halt all route execution while paused
+    pub fn set_paused(ctx: Context<UpdateConfig>, paused: bool) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.paused = paused;
+        // Unpausing is also how a tripped circuit breaker is reset
+        if !paused {
+            ctx.accounts.config.consecutive_failures = 0;
+        }
+        msg!("🛑 Program paused: {}", paused);
+        Ok(())
+    }
 
-#[derive(Debug)]
-pub struct SwapResult {
-    pub success: bool,
-    pub output_amount: u64,
-    pub slippage_bps: u16,
-}
+    // 🔦 CIRCUIT BREAKER: pause after `threshold` consecutive failed executions (0 = off)
+    //
+    // A failed execution reverts its own transaction, so it can't count itself.
+    // Instead the reporter, typically the operator's executor key, reports the
+    // outcome of each execution it submits; failures accumulate, a success
+    // clears them, and reaching the threshold pauses the program until the
+    // admins unpause it.
+    pub fn set_circuit_breaker(
+        ctx: Context<UpdateConfig>,
+        reporter: Option<Pubkey>,
+        threshold: u16,
+    ) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        require!(reporter.is_some() || threshold == 0, ArbitrageError::InvalidBreakerReporter);
+        let config = &mut ctx.accounts.config;
+        config.breaker_reporter = reporter;
+        config.breaker_threshold = threshold;
+        config.consecutive_failures = 0;
+        msg!("🔦 Circuit breaker: {:?} reports, trips after {} failures", reporter, threshold);
+        Ok(())
+    }
 
-// 📻 EVENTS
-#[event]
-pub struct ArbitrageExecuted {
-    pub user: Pubkey,
-    pub start_amount: u64,
-    pub final_amount: u64,
-    pub profit: u64,
-    pub profit_bps: u64,
-    pub steps: u8,
-}
+    pub fn report_execution(ctx: Context<ReportExecution>, succeeded: bool) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(
+            config.breaker_reporter == Some(ctx.accounts.reporter.key()),
+            ArbitrageError::InvalidBreakerReporter
+        );
+        if succeeded {
+            config.consecutive_failures = 0;
+            return Ok(());
+        }
 
-// ❌ ERROR HANDLING
-#[error_code]
-pub enum ArbitrageError {
-    #[msg("Route must have at least 3 steps")]
-    RouteTooShort,
-    #[msg("Route cannot exceed 6 steps")]
-    RouteTooLong,
-    #[msg("Invalid minimum profit specified")]
-    InvalidMinProfit,
-    #[msg("Swap execution failed")]
-    SwapFailed,
-    #[msg("Insufficient profit achieved")]
-    InsufficientProfit,
-    #[msg("Slippage exceeded maximum")]
-    SlippageExceeded,
-}
\ No newline at end of file
+        config.consecutive_failures = config.consecutive_failures.saturating_add(1);
+        if config.breaker_threshold > 0 && config.consecutive_failures >= config.breaker_threshold && !config.paused {
+            config.paused = true;
+            emit!(CircuitBreakerTripped { consecutive_failures: config.consecutive_failures });
+            msg!("🔦 Circuit breaker tripped after {} consecutive failures", config.consecutive_failures);
+        }
+        Ok(())
+    }
+
+    // 💰 PROTOCOL FEE: share of each route's profit sent to the treasury
+    pub fn set_fee_bps(ctx: Context<UpdateConfig>, fee_bps: u16) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        require!(fee_bps <= 10000, ArbitrageError::InvalidFee);
+        ctx.accounts.config.fee_bps = fee_bps;
+        msg!("💰 Protocol fee set to {}bps", fee_bps);
+        Ok(())
+    }
+
+    // 📏 ROUTE LENGTH BOUNDS
+    pub fn set_route_length_bounds(
+        ctx: Context<UpdateConfig>,
+        min_route_len: u8,
+        max_route_len: u8,
+    ) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        // A cycle needs at least two legs to leave and return to a mint
+        require!(min_route_len >= 2, ArbitrageError::InvalidRouteBounds);
+        require!(min_route_len <= max_route_len, ArbitrageError::InvalidRouteBounds);
+
+        let config = &mut ctx.accounts.config;
+        config.min_route_len = min_route_len;
+        config.max_route_len = max_route_len;
+        msg!("📏 Route length bounds set to {}..={}", min_route_len, max_route_len);
+        Ok(())
+    }
+
+    // ⏱️ COMPUTE GUARD: compute units that must remain before each leg (0 = off)
+    pub fn set_compute_guard(ctx: Context<UpdateConfig>, min_compute_per_leg: u64) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.min_compute_per_leg = min_compute_per_leg;
+        msg!("⏱️ Minimum compute per leg set to {}", min_compute_per_leg);
+        Ok(())
+    }
+
+    // 0 disables the cooldown
+    pub fn set_cooldown(ctx: Context<UpdateConfig>, cooldown_slots: u64) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.cooldown_slots = cooldown_slots;
+        msg!("🧊 Per-user cooldown set to {} slots", cooldown_slots);
+        Ok(())
+    }
+
+    // 🔇 LOGGING: progress logs on the execution path (off saves compute)
+    pub fn set_verbose_logs(ctx: Context<UpdateConfig>, verbose_logs: bool) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.verbose_logs = verbose_logs;
+        msg!("🔇 Verbose logs: {}", verbose_logs);
+        Ok(())
+    }
+
+    // 🧹 RESIDUAL TOKEN CHECK: fail routes that leave tokens in intermediate accounts
+    pub fn set_residual_token_check(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.check_residual_tokens = enabled;
+        msg!("🧹 Residual token check: {}", enabled);
+        Ok(())
+    }
+
+    // 📸 POOL SNAPSHOTS: emit vault balances around every leg that has live_reserves
+    pub fn set_pool_snapshots(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.pool_snapshots = enabled;
+        msg!("📸 Pool snapshots: {}", enabled);
+        Ok(())
+    }
+
+    // ⏱️ LEG METERING: emit the compute units each leg's swap consumed
+    pub fn set_leg_metering(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.meter_legs = enabled;
+        msg!("⏱️ Leg metering: {}", enabled);
+        Ok(())
+    }
+
+    // 🧠 STRATEGIST: share of each route's profit (after the protocol fee) paid to a strategist
+    pub fn set_strategist(ctx: Context<UpdateConfig>, strategist: Option<Pubkey>, strategist_bps: u16) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        require!(strategist_bps <= 10000, ArbitrageError::InvalidFee);
+        require!(strategist.is_some() || strategist_bps == 0, ArbitrageError::InvalidFee);
+        ctx.accounts.config.strategist = strategist;
+        ctx.accounts.config.strategist_bps = strategist_bps;
+        msg!("🧠 Strategist set to {:?} at {}bps", strategist, strategist_bps);
+        Ok(())
+    }
+
+    // 🥪 SELF-SANDWICH OVERRIDE: let routes trade one pool in both directions
+    pub fn set_allow_self_sandwich(ctx: Context<UpdateConfig>, allowed: bool) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.allow_self_sandwich = allowed;
+        msg!("🥪 Self-sandwich routes allowed: {}", allowed);
+        Ok(())
+    }
+
+    // 📦 ROUTE DATA CAP: largest route_data blob a single step (or split portion) may carry
+    pub fn set_max_route_data_len(ctx: Context<UpdateConfig>, max_route_data_len: u16) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        // Every venue but Jupiter reads at least a byte of route_data
+        require!(max_route_data_len > 0, ArbitrageError::RouteDataTooLarge);
+        ctx.accounts.config.max_route_data_len = max_route_data_len;
+        msg!("📦 Max route data per step: {} bytes", max_route_data_len);
+        Ok(())
+    }
+
+    // ➿ REPEATED MINTS: let routes pass through the same mint more than once
+    pub fn set_allow_repeated_mints(ctx: Context<UpdateConfig>, allowed: bool) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.allow_repeated_mints = allowed;
+        msg!("➿ Repeated mints allowed: {}", allowed);
+        Ok(())
+    }
+
+    // 🏛️ VENUE CAP: most distinct DEXes one route may touch (0 = off)
+    pub fn set_max_distinct_dexes(ctx: Context<UpdateConfig>, max_distinct_dexes: u8) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.max_distinct_dexes = max_distinct_dexes;
+        msg!("🏛️ Max distinct DEXes per route: {}", max_distinct_dexes);
+        Ok(())
+    }
+
+    // 🔌 CPI CAP: most swap CPIs one run may make (0 = off)
+    pub fn set_max_cpis(ctx: Context<UpdateConfig>, max_cpis: u8) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        ctx.accounts.config.max_cpis = max_cpis;
+        msg!("🔌 Maximum CPIs per route set to {}", max_cpis);
+        Ok(())
+    }
+
+    // 📡 ORACLE BOUNDS: how far below the oracle a leg may fill, and how old a price may be
+    pub fn set_oracle_bounds(
+        ctx: Context<UpdateConfig>,
+        max_deviation_bps: u16,
+        max_age_secs: u64,
+        max_staleness_slots: u64,
+    ) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        require!(max_deviation_bps <= 10000, ArbitrageError::InvalidSlippage);
+
+        let config = &mut ctx.accounts.config;
+        config.oracle_max_deviation_bps = max_deviation_bps;
+        config.oracle_max_age_secs = max_age_secs;
+        config.max_oracle_staleness_slots = max_staleness_slots;
+        msg!(
+            "📡 Oracle bounds set to {}bps, {}s, {} slots",
+            max_deviation_bps, max_age_secs, max_staleness_slots
+        );
+        Ok(())
+    }
+
+    // 📡 ORACLE CONFIDENCE: reject feeds whose confidence band is wider than this
+    pub fn set_max_oracle_confidence(ctx: Context<UpdateConfig>, max_confidence_bps: u16) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        require!(max_confidence_bps <= 10000, ArbitrageError::InvalidSlippage);
+
+        ctx.accounts.config.max_oracle_confidence_bps = max_confidence_bps;
+        msg!("📡 Max oracle confidence set to {}bps", max_confidence_bps);
+        Ok(())
+    }
+
+    // 🛟 RESCUE: move tokens out of a token account owned by the config PDA
+    pub fn rescue_tokens(ctx: Context<RescueTokens>, amount: u64) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[CONFIG_SEED, &[ctx.accounts.config.bump]]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.source.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.recipient.to_account_info(),
+                    authority: ctx.accounts.config.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        msg!(
+            "🛟 Rescued {} of {} from {} to {}",
+            amount,
+            ctx.accounts.mint.key(),
+            ctx.accounts.source.key(),
+            ctx.accounts.recipient.key()
+        );
+        Ok(())
+    }
+
+    // 🏛️ FEE WITHDRAWAL: pay accumulated protocol fees out of the treasury
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>, amount: u64) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[CONFIG_SEED, &[ctx.accounts.config.bump]]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.treasury_token_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.config.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            ctx.accounts.token_mint.decimals,
+        )?;
+
+        emit!(FeesWithdrawn {
+            mint: ctx.accounts.token_mint.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+        });
+        msg!("🏛️ Withdrew {} of {} in fees to {}", amount, ctx.accounts.token_mint.key(), ctx.accounts.destination.key());
+        Ok(())
+    }
+
+    // 📒 DEX REGISTRY: only whitelisted programs can be CPI'd into
+    pub fn initialize_dex_registry(ctx: Context<InitializeDexRegistry>) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+
+        let registry = &mut ctx.accounts.dex_registry;
+        registry.entries = Vec::new();
+        registry.bump = ctx.bumps.dex_registry;
+
+        msg!("📒 DEX registry initialized");
+        Ok(())
+    }
+
+    pub fn register_dex(
+        ctx: Context<UpdateDexRegistry>,
+        dex: DexType,
+        program_id: Pubkey,
+        fee_bps: u16, // The venue's swap fee, taken off expected_rate when sizing min_output
+        max_slippage_bps: u16, // Tightest slippage any leg on this venue may use (10000 = no cap)
+    ) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        let registry = &mut ctx.accounts.dex_registry;
+        require!(!registry.is_allowed(dex, program_id), ArbitrageError::DexAlreadyRegistered);
+        require!(registry.entries.len() < MAX_REGISTERED_DEXES, ArbitrageError::DexRegistryFull);
+        require!(fee_bps <= 10000, ArbitrageError::InvalidFee);
+        require!(max_slippage_bps <= 10000, ArbitrageError::InvalidSlippage);
+
+        registry.entries.push(DexEntry { dex, program_id, fee_bps, max_slippage_bps });
+        msg!(
+            "📒 Registered {:?} program {} ({}bps fee, {}bps slippage cap)",
+            dex, program_id, fee_bps, max_slippage_bps
+        );
+        Ok(())
+    }
+
+    // Takes DexEntry rather than (DexType, Pubkey) tuples so the IDL stays expressible;
+    // the whole batch is rejected if any entry is invalid.
+    pub fn register_dexes(ctx: Context<UpdateDexRegistry>, entries: Vec<DexEntry>) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        require!(entries.len() <= MAX_DEX_BATCH, ArbitrageError::DexBatchTooLarge);
+        let registry = &mut ctx.accounts.dex_registry;
+        require!(
+            registry.entries.len() + entries.len() <= MAX_REGISTERED_DEXES,
+            ArbitrageError::DexRegistryFull
+        );
+
+        for entry in entries {
+            require!(!registry.is_allowed(entry.dex, entry.program_id), ArbitrageError::DexAlreadyRegistered);
+            require!(entry.fee_bps <= 10000, ArbitrageError::InvalidFee);
+            require!(entry.max_slippage_bps <= 10000, ArbitrageError::InvalidSlippage);
+            msg!(
+                "📒 Registered {:?} program {} ({}bps fee, {}bps slippage cap)",
+                entry.dex, entry.program_id, entry.fee_bps, entry.max_slippage_bps
+            );
+            registry.entries.push(entry);
+        }
+        Ok(())
+    }
+
+    pub fn set_dex_slippage_cap(
+        ctx: Context<UpdateDexRegistry>,
+        dex: DexType,
+        program_id: Pubkey,
+        max_slippage_bps: u16,
+    ) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        require!(max_slippage_bps <= 10000, ArbitrageError::InvalidSlippage);
+        let entry = ctx
+            .accounts
+            .dex_registry
+            .entries
+            .iter_mut()
+            .find(|entry| entry.dex == dex && entry.program_id == program_id)
+            .ok_or(ArbitrageError::DexNotRegistered)?;
+
+        entry.max_slippage_bps = max_slippage_bps;
+        msg!("📒 {:?} program {} slippage cap set to {}bps", dex, program_id, max_slippage_bps);
+        Ok(())
+    }
+
+    pub fn deregister_dex(ctx: Context<UpdateDexRegistry>, dex: DexType, program_id: Pubkey) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        let registry = &mut ctx.accounts.dex_registry;
+        let position = registry
+            .entries
+            .iter()
+            .position(|entry| entry.dex == dex && entry.program_id == program_id)
+            .ok_or(ArbitrageError::DexNotRegistered)?;
+
+        registry.entries.remove(position);
+        msg!("📒 Deregistered {:?} program {}", dex, program_id);
+        Ok(())
+    }
+
+    // 🏦 TRADING VAULT: a program-owned account set to trade from instead of the user's ATAs
+    //
+    // Creates the user's vault and its token account for token_mint. Further
+    // mints only need the vault's associated token account, which anyone can create.
+    pub fn init_vault(ctx: Context<InitVault>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.owner = ctx.accounts.owner.key();
+        vault.bump = ctx.bumps.vault;
+
+        msg!("🏦 Vault {} initialized for {}", vault.key(), vault.owner);
+        Ok(())
+    }
+
+    pub fn deposit_to_vault(ctx: Context<VaultTransfer>, amount: u64) -> Result<()> {
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.token_mint.decimals,
+        )?;
+
+        msg!("🏦 Deposited {} of {} to the vault", amount, ctx.accounts.token_mint.key());
+        Ok(())
+    }
+
+    pub fn withdraw_from_vault(ctx: Context<VaultTransfer>, amount: u64) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[VAULT_SEED, owner.as_ref(), &[ctx.accounts.vault.bump]]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.owner_token_account.to_account_info(),
+                    authority: ctx.accounts.vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            ctx.accounts.token_mint.decimals,
+        )?;
+
+        msg!("🏦 Withdrew {} of {} from the vault", amount, ctx.accounts.token_mint.key());
+        Ok(())
+    }
+
+    // 🪙 MINT ALLOWLIST: only listed mints may appear anywhere in a route
+    pub fn initialize_mint_allowlist(ctx: Context<InitializeMintAllowlist>) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+
+        let allowlist = &mut ctx.accounts.mint_allowlist;
+        allowlist.mints = Vec::new();
+        allowlist.bump = ctx.bumps.mint_allowlist;
+
+        msg!("🪙 Mint allowlist initialized");
+        Ok(())
+    }
+
+    pub fn allow_mint(ctx: Context<UpdateMintAllowlist>, mint: Pubkey) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        let allowlist = &mut ctx.accounts.mint_allowlist;
+        require!(!allowlist.is_allowed(mint), ArbitrageError::MintAlreadyAllowed);
+        require!(allowlist.mints.len() < MAX_ALLOWED_MINTS, ArbitrageError::MintAllowlistFull);
+
+        allowlist.mints.push(mint);
+        msg!("🪙 Allowed mint {}", mint);
+        Ok(())
+    }
+
+    pub fn disallow_mint(ctx: Context<UpdateMintAllowlist>, mint: Pubkey) -> Result<()> {
+        require_admin_approval(&ctx.accounts.config, ctx.remaining_accounts)?;
+        let allowlist = &mut ctx.accounts.mint_allowlist;
+        let position = allowlist
+            .mints
+            .iter()
+            .position(|allowed| *allowed == mint)
+            .ok_or(ArbitrageError::MintNotAllowed)?;
+
+        allowlist.mints.remove(position);
+        msg!("🪙 Disallowed mint {}", mint);
+        Ok(())
+    }
+
+    // 💬 QUOTE: projected output of every leg, before slippage
+    //
+    // Each leg is priced as execute_dex_swap sizes min_output: expected_rate,
+    // less the registry's fee for the venue. Written as a borsh Vec<u64>
+    // return value; no CPIs are made.
+    pub fn quote_route(
+        ctx: Context<QuoteRoute>,
+        route: Vec<VersionedSwapStep>,
+        input_amount: u64,
+    ) -> Result<()> {
+        let route = decode_route(&route)?;
+        validate_route_length(&route, &ctx.accounts.config)?;
+        validate_route_continuity(&route, ctx.accounts.token_mint.key())?;
+
+        let registry = &ctx.accounts.dex_registry;
+        let projected_outputs =
+            project_leg_outputs(&route, input_amount, &ctx.accounts.config, |step| registry.step_fee_bps(step))?;
+
+        let mut return_data = Vec::new();
+        projected_outputs.serialize(&mut return_data)?;
+        set_return_data(&return_data);
+        Ok(())
+    }
+
+    // ⏸️ ROUTE CHECKPOINT: opt-in storage for partially executed routes
+    pub fn open_route_checkpoint(ctx: Context<OpenRouteCheckpoint>) -> Result<()> {
+        let checkpoint = &mut ctx.accounts.route_checkpoint;
+        checkpoint.active = false;
+        checkpoint.bump = ctx.bumps.route_checkpoint;
+        Ok(())
+    }
+
+    // Abandons any recorded progress; tokens already swapped stay in the user's accounts
+    pub fn close_route_checkpoint(_ctx: Context<CloseRouteCheckpoint>) -> Result<()> {
+        msg!("⏸️ Route checkpoint closed");
+        Ok(())
+    }
+
+    // 🔄 EMERGENCY FUNCTION: clear out the caller's leftover program state
+    //
+    // Closes whichever of the caller's execution lock and route checkpoint are
+    // passed, plus any of the caller's token accounts given as remaining_accounts
+    // (WSOL accounts unwrap; others must be empty), refunding all rent to them.
+    pub fn emergency_cancel<'info>(ctx: Context<'_, '_, 'info, 'info, EmergencyCancel<'info>>) -> Result<()> {
+        msg!("🚨 Emergency cancel triggered");
+
+        if !ctx.remaining_accounts.is_empty() {
+            let token_program = ctx
+                .accounts
+                .token_program
+                .as_ref()
+                .ok_or(ArbitrageError::MissingTokenAccount)?;
+            for token_account in ctx.remaining_accounts {
+                require_keys_eq!(
+                    load_token_account(token_account)?.owner,
+                    ctx.accounts.user.key(),
+                    ArbitrageError::TokenAccountMismatch
+                );
+                token_interface::close_account(CpiContext::new(
+                    token_program.to_account_info(),
+                    CloseAccount {
+                        account: token_account.clone(),
+                        destination: ctx.accounts.user.to_account_info(),
+                        authority: ctx.accounts.user.to_account_info(),
+                    },
+                ))?;
+                msg!("Closed token account {}", token_account.key());
+            }
+        }
+
+        // The lock and checkpoint themselves are closed by their account constraints
+        if ctx.accounts.execution_lock.is_some() {
+            msg!("Closed execution lock");
+        }
+        if ctx.accounts.route_checkpoint.is_some() {
+            msg!("Closed route checkpoint");
+        }
+        Ok(())
+    }
+}
+
+// 🔐 ADMIN MULTISIG
+fn validate_admin_signers(signers: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(!signers.is_empty() && signers.len() <= MAX_ADMIN_SIGNERS, ArbitrageError::InvalidMultisig);
+    require!(threshold > 0 && threshold as usize <= signers.len(), ArbitrageError::InvalidMultisig);
+    for (index, signer) in signers.iter().enumerate() {
+        require!(!signers[..index].contains(signer), ArbitrageError::InvalidMultisig);
+    }
+    Ok(())
+}
+
+// Counts distinct configured signers that signed; repeating an account doesn't add approvals
+fn require_admin_approval(config: &ProgramConfig, accounts: &[AccountInfo]) -> Result<()> {
+    let approvals = config
+        .signers
+        .iter()
+        .filter(|signer| accounts.iter().any(|account| account.is_signer && account.key == *signer))
+        .count();
+    require!(approvals >= config.threshold as usize, ArbitrageError::Unauthorized);
+    Ok(())
+}
+
+// 🏷️ VERSIONED STEPS
+//
+// Steps arrive as (version, borsh bytes) so new SwapStep fields don't break
+// clients still encoding an older layout:
+//   v1  the original layout: mints, dex, program id, rate and route data
+//   v2  adds the per-step options, up to the pool liquidity floor
+//   v3  adds live reserve quoting
+//   v4  adds a precomputed expected_output
+//   v5  the current SwapStep, adding explicit input and output token accounts
+fn decode_route(route: &[VersionedSwapStep]) -> Result<Vec<SwapStep>> {
+    route.iter().map(VersionedSwapStep::decode).collect()
+}
+
+// The reversed cycle is validated like any other route afterwards, continuity included.
+// expected_rate is inverted at the same x1000 precision, which rounds toward a looser bound.
+// A rate the inversion would divide by zero, or round to zero, is rejected here,
+// before validate_step_parameters ever sees the reversed route.
+fn orient_route(route: Vec<SwapStep>, direction: RouteDirection) -> Result<Vec<SwapStep>> {
+    match direction {
+        RouteDirection::Forward => Ok(route),
+        RouteDirection::Reverse => route
+            .into_iter()
+            .rev()
+            .map(|step| {
+                require!(step.expected_rate > 0, ArbitrageError::InvalidRate);
+                let expected_rate = checked_mul_div(1000, 1000, step.expected_rate)?;
+                require!(expected_rate > 0, ArbitrageError::InvalidRate);
+                Ok(SwapStep {
+                    input_mint: step.output_mint,
+                    output_mint: step.input_mint,
+                    expected_rate,
+                    input_account: step.output_account,
+                    output_account: step.input_account,
+                    live_reserves: step.live_reserves.map(|reserves| LiveReserves {
+                        input_vault: reserves.output_vault,
+                        output_vault: reserves.input_vault,
+                    }),
+                    // The feed is unchanged, but the leg now converts the other way
+                    oracle: step.oracle.map(|oracle| StepOracle {
+                        inverted: !oracle.inverted,
+                        input_decimals: oracle.output_decimals,
+                        output_decimals: oracle.input_decimals,
+                        ..oracle
+                    }),
+                    // Quoted for the forward leg's input, which the reversed leg doesn't receive
+                    expected_output: None,
+                    ..step
+                })
+            })
+            .collect(),
+    }
+}
+
+impl VersionedSwapStep {
+    pub fn decode(&self) -> Result<SwapStep> {
+        match self.version {
+            1 => Ok(SwapStepV1::try_from_slice(&self.data)?.into()),
+            2 => Ok(SwapStepV2::try_from_slice(&self.data)?.into()),
+            3 => Ok(SwapStepV3::try_from_slice(&self.data)?.into()),
+            4 => Ok(SwapStepV4::try_from_slice(&self.data)?.into()),
+            5 => Ok(SwapStep::try_from_slice(&self.data)?),
+            _ => err!(ArbitrageError::UnsupportedStepVersion),
+        }
+    }
+}
+
+impl From<SwapStepV1> for SwapStep {
+    fn from(step: SwapStepV1) -> Self {
+        SwapStep {
+            input_mint: step.input_mint,
+            output_mint: step.output_mint,
+            dex: step.dex,
+            program_id: step.program_id,
+            expected_rate: step.expected_rate,
+            route_data: step.route_data,
+            slippage_bps: None,
+            account_indices: None,
+            split: None,
+            remainder_dex: None,
+            exact_output: None,
+            oracle: None,
+            liquidity: None,
+            live_reserves: None,
+            expected_output: None,
+            input_account: None,
+            output_account: None,
+        }
+    }
+}
+
+impl From<SwapStepV3> for SwapStep {
+    fn from(step: SwapStepV3) -> Self {
+        SwapStep {
+            input_mint: step.input_mint,
+            output_mint: step.output_mint,
+            dex: step.dex,
+            program_id: step.program_id,
+            expected_rate: step.expected_rate,
+            route_data: step.route_data,
+            slippage_bps: step.slippage_bps,
+            account_indices: step.account_indices,
+            split: step.split,
+            remainder_dex: step.remainder_dex,
+            exact_output: step.exact_output,
+            oracle: step.oracle,
+            liquidity: step.liquidity,
+            live_reserves: step.live_reserves,
+            expected_output: None,
+            input_account: None,
+            output_account: None,
+        }
+    }
+}
+
+impl From<SwapStepV4> for SwapStep {
+    fn from(step: SwapStepV4) -> Self {
+        SwapStep {
+            input_mint: step.input_mint,
+            output_mint: step.output_mint,
+            dex: step.dex,
+            program_id: step.program_id,
+            expected_rate: step.expected_rate,
+            route_data: step.route_data,
+            slippage_bps: step.slippage_bps,
+            account_indices: step.account_indices,
+            split: step.split,
+            remainder_dex: step.remainder_dex,
+            exact_output: step.exact_output,
+            oracle: step.oracle,
+            liquidity: step.liquidity,
+            live_reserves: step.live_reserves,
+            expected_output: step.expected_output,
+            input_account: None,
+            output_account: None,
+        }
+    }
+}
+
+impl From<SwapStepV2> for SwapStep {
+    fn from(step: SwapStepV2) -> Self {
+        SwapStep {
+            input_mint: step.input_mint,
+            output_mint: step.output_mint,
+            dex: step.dex,
+            program_id: step.program_id,
+            expected_rate: step.expected_rate,
+            route_data: step.route_data,
+            slippage_bps: step.slippage_bps,
+            account_indices: step.account_indices,
+            split: step.split,
+            remainder_dex: step.remainder_dex,
+            exact_output: step.exact_output,
+            oracle: step.oracle,
+            liquidity: step.liquidity,
+            live_reserves: None,
+            expected_output: None,
+            input_account: None,
+            output_account: None,
+        }
+    }
+}
+
+// 🛡️ ROUTE VALIDATION
+fn validate_route_length(route: &[SwapStep], config: &ProgramConfig) -> Result<()> {
+    require!(route.len() >= config.min_route_len as usize, ArbitrageError::RouteTooShort);
+    require!(route.len() <= config.max_route_len as usize, ArbitrageError::RouteTooLong);
+    Ok(())
+}
+
+fn validate_route_continuity(route: &[SwapStep], start_mint: Pubkey) -> Result<()> {
+    // token_mint must be what the route actually trades, not just any mint the ATA matches
+    let first = route.first().ok_or(ArbitrageError::RouteTooShort)?;
+    require_keys_eq!(first.input_mint, start_mint, ArbitrageError::MintMismatch);
+
+    // Each step must spend exactly the token the previous step produced. This
+    // chaining is also why legs are never reordered on-chain: no two legs of a
+    // continuous path are independent, and the only orderings that keep it
+    // continuous are rotations, which would no longer start from start_mint.
+    // Parallel venues for one hop are expressed as a split leg instead.
+    for pair in route.windows(2) {
+        require_keys_eq!(pair[0].output_mint, pair[1].input_mint, ArbitrageError::DiscontinuousRoute);
+    }
+
+    Ok(())
+}
+
+fn validate_route_cycle(route: &[SwapStep]) -> Result<()> {
+    let first = route.first().ok_or(ArbitrageError::RouteTooShort)?;
+    let last = route.last().ok_or(ArbitrageError::RouteTooShort)?;
+    require_keys_eq!(last.output_mint, first.input_mint, ArbitrageError::RouteNotCyclic);
+    Ok(())
+}
+
+// A simple cycle visits each mint once, the start mint again only at the end.
+// One that comes back through a mint midway (A -> B -> A -> C -> A) is a
+// figure eight: usually a client bug, or two sandwiched cycles posing as one.
+fn validate_no_repeated_mints(route: &[SwapStep], config: &ProgramConfig) -> Result<()> {
+    if config.allow_repeated_mints {
+        return Ok(());
+    }
+    for (step_index, step) in route.iter().enumerate() {
+        require!(
+            route[..step_index].iter().all(|earlier| earlier.input_mint != step.input_mint),
+            ArbitrageError::RepeatedMint
+        );
+    }
+    Ok(())
+}
+
+fn validate_step_parameters(route: &[SwapStep]) -> Result<()> {
+    for (step_index, step) in route.iter().enumerate() {
+        // A zero rate zeroes min_output and with it the leg's slippage protection
+        require!(step.expected_rate > 0, ArbitrageError::InvalidRate);
+        if let Some(slippage_bps) = step.slippage_bps {
+            require!(slippage_bps <= 10000, ArbitrageError::InvalidSlippage);
+        }
+        if let Some(split) = &step.split {
+            require!(split.len() >= 2, ArbitrageError::InvalidSplit);
+            require!(split.iter().all(|leg| leg.weight_bps > 0), ArbitrageError::InvalidSplit);
+            let total_weight: u32 = split.iter().map(|leg| leg.weight_bps as u32).sum();
+            require!(total_weight == 10000, ArbitrageError::InvalidSplit);
+        }
+        if let Some(remainder_dex) = step.remainder_dex {
+            require!(remainder_dex != step.dex, ArbitrageError::InvalidRemainderVenue);
+            require!(step.split.is_none(), ArbitrageError::InvalidRemainderVenue);
+        }
+        if step.exact_output.is_some() {
+            // Only the final leg settles a fixed amount, and only on venues with a base-out swap
+            require!(step_index == route.len() - 1, ArbitrageError::InvalidExactOutput);
+            require!(
+                matches!(step.dex, DexType::Raydium | DexType::Orca),
+                ArbitrageError::InvalidExactOutput
+            );
+            require!(
+                step.split.is_none() && step.remainder_dex.is_none(),
+                ArbitrageError::InvalidExactOutput
+            );
+        }
+    }
+    Ok(())
+}
+
+// Counts every venue a route could swap on: each split portion in place of its
+// leg's dex, plus remainder venues, since a leg may end up filling there
+fn validate_distinct_dexes(route: &[SwapStep], config: &ProgramConfig) -> Result<()> {
+    if config.max_distinct_dexes == 0 {
+        return Ok(());
+    }
+    let mut dexes: Vec<DexType> = Vec::new();
+    for step in route {
+        let step_dexes: Vec<DexType> = match &step.split {
+            Some(split) => split.iter().map(|leg| leg.dex).collect(),
+            None => std::iter::once(step.dex).chain(step.remainder_dex).collect(),
+        };
+        for dex in step_dexes {
+            if !dexes.contains(&dex) {
+                dexes.push(dex);
+            }
+        }
+    }
+    require!(dexes.len() <= config.max_distinct_dexes as usize, ArbitrageError::TooManyDexes);
+    Ok(())
+}
+
+// route_data is copied into each leg's instruction data, so an oversized blob
+// burns compute and can push the transaction past its size limit
+fn validate_route_data_size(route: &[SwapStep], config: &ProgramConfig) -> Result<()> {
+    let max_len = config.max_route_data_len as usize;
+    for step in route {
+        require!(step.route_data.len() <= max_len, ArbitrageError::RouteDataTooLarge);
+        if let Some(split) = &step.split {
+            require!(
+                split.iter().all(|leg| leg.route_data.len() <= max_len),
+                ArbitrageError::RouteDataTooLarge
+            );
+        }
+    }
+    Ok(())
+}
+
+// Honeypot tokens can freeze or tax whatever touches them; keep routes to vetted mints
+fn validate_route_mints(route: &[SwapStep], allowlist: &MintAllowlist) -> Result<()> {
+    for step in route {
+        require!(allowlist.is_allowed(step.input_mint), ArbitrageError::MintNotAllowed);
+        require!(allowlist.is_allowed(step.output_mint), ArbitrageError::MintNotAllowed);
+    }
+    Ok(())
+}
+
+// 🧾 TOKEN ACCOUNT RESOLUTION
+//
+// Reads the route's token and DEX accounts from the front of `accounts`
+// (all of remaining_accounts for a single route); account_indices still
+// index into the full remaining_accounts.
+fn resolve_route_accounts<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    route: &[SwapStep],
+    accounts: &'info [AccountInfo<'info>],
+) -> Result<RouteAccounts<'info>> {
+    // Steps that name their output account take no positional token slot
+    let positional_count = route.iter().filter(|step| step.output_account.is_none()).count();
+    require!(accounts.len() >= positional_count, ArbitrageError::MissingTokenAccount);
+    let (mut token_accounts, mut dex_accounts) = accounts.split_at(positional_count);
+
+    let trader = trading_authority(ctx).key();
+    let indexed_account = |index: u8| -> Result<&'info AccountInfo<'info>> {
+        ctx.remaining_accounts
+            .get(index as usize)
+            .ok_or(error!(ArbitrageError::InvalidAccountIndex))
+    };
+    let check_token_account = |info: &AccountInfo, mint: Pubkey| -> Result<()> {
+        let token_account = load_token_account(info)?;
+        require_keys_eq!(token_account.owner, trader, ArbitrageError::TokenAccountMismatch);
+        require_keys_eq!(token_account.mint, mint, ArbitrageError::TokenAccountMismatch);
+        Ok(())
+    };
+    let mut inputs = Vec::with_capacity(route.len());
+    let mut outputs = Vec::with_capacity(route.len());
+    let mut dex = Vec::with_capacity(route.len());
+
+    for (step_index, step) in route.iter().enumerate() {
+        let input = match step.input_account {
+            Some(index) => {
+                let info = indexed_account(index)?;
+                check_token_account(info, step.input_mint)?;
+                // Profit is measured on user_token_account, so the route has to spend from it
+                if step_index == 0 {
+                    require_keys_eq!(
+                        info.key(),
+                        ctx.accounts.user_token_account.key(),
+                        ArbitrageError::TokenAccountMismatch
+                    );
+                }
+                Some(info)
+            }
+            None => None,
+        };
+        inputs.push(input);
+
+        let output = match step.output_account {
+            Some(index) => indexed_account(index)?,
+            None => {
+                let (info, rest) = token_accounts.split_first().ok_or(ArbitrageError::MissingTokenAccount)?;
+                token_accounts = rest;
+                info
+            }
+        };
+        check_token_account(output, step.output_mint)?;
+        outputs.push(output);
+
+        let count = dex_account_count(step)?;
+        let step_dex_accounts = match &step.account_indices {
+            Some(indices) => {
+                require!(indices.len() == count, ArbitrageError::MissingDexAccounts);
+                indices
+                    .iter()
+                    .map(|&index| {
+                        ctx.remaining_accounts
+                            .get(index as usize)
+                            .cloned()
+                            .ok_or(error!(ArbitrageError::InvalidAccountIndex))
+                    })
+                    .collect::<Result<Vec<_>>>()?
+            }
+            None => {
+                require!(dex_accounts.len() >= count, ArbitrageError::MissingDexAccounts);
+                let (step_dex_accounts, rest) = dex_accounts.split_at(count);
+                dex_accounts = rest;
+                step_dex_accounts.to_vec()
+            }
+        };
+        dex.push(step_dex_accounts);
+    }
+
+    if let Some((first, second)) = find_self_sandwich(route, &dex) {
+        require!(ctx.accounts.config.allow_self_sandwich, ArbitrageError::SelfSandwichDetected);
+        msg!("⚠️ Legs {} and {} trade the same pool in opposite directions", first, second);
+    }
+
+    // Closing the cycle into the starting account keeps final - start == profit
+    let last_output = outputs.last().ok_or(ArbitrageError::RouteTooShort)?;
+    require_keys_eq!(
+        last_output.key(),
+        ctx.accounts.user_token_account.key(),
+        ArbitrageError::TokenAccountMismatch
+    );
+
+    Ok(RouteAccounts { inputs, outputs, dex, tail: dex_accounts })
+}
+
+// Where in remaining_accounts a single route's step receives its output
+fn output_account_index(route: &[SwapStep], step_index: usize) -> usize {
+    match route[step_index].output_account {
+        Some(index) => index as usize,
+        None => route[..step_index].iter().filter(|step| step.output_account.is_none()).count(),
+    }
+}
+
+// Two legs that buy and then sell through the same pool pay its fee twice to
+// undo their own price impact, which is almost always a loss
+fn find_self_sandwich(route: &[SwapStep], dex_accounts: &[Vec<AccountInfo>]) -> Option<(usize, usize)> {
+    let pools: Vec<Option<Pubkey>> = route
+        .iter()
+        .zip(dex_accounts)
+        .map(|(step, accounts)| {
+            // A split leg's accounts belong to its portions, not step.dex
+            if step.split.is_some() {
+                return None;
+            }
+            pool_account_index(step.dex).and_then(|index| accounts.get(index)).map(|info| info.key())
+        })
+        .collect();
+
+    for (first, first_pool) in pools.iter().enumerate() {
+        let Some(first_pool) = first_pool else { continue };
+        for (second, second_pool) in pools.iter().enumerate().skip(first + 1) {
+            if second_pool.as_ref() == Some(first_pool)
+                && route[first].input_mint == route[second].output_mint
+                && route[first].output_mint == route[second].input_mint
+            {
+                return Some((first, second));
+            }
+        }
+    }
+    None
+}
+
+// Position of the pool (or market) account in each venue's DEX accounts
+fn pool_account_index(dex: DexType) -> Option<usize> {
+    match dex {
+        DexType::Raydium => Some(2),
+        DexType::Orca => Some(2),
+        DexType::Meteora => Some(1),
+        DexType::Phoenix => Some(2),
+        DexType::OpenBookV2 => Some(1),
+        DexType::Lifinity => Some(2),
+        DexType::Saber => Some(1),
+        DexType::Crema => Some(2),
+        DexType::Invariant => Some(2),
+        // Aggregated and custom venues don't expose a single pool account
+        DexType::Jupiter | DexType::Custom => None,
+    }
+}
+
+// Preflight: how many remaining_accounts the route's positional token and DEX
+// slots take, after checking that every index it names is in range
+fn positional_account_count(route: &[SwapStep], available: usize) -> Result<usize> {
+    let mut count = 0;
+    for step in route {
+        let mut indices = step.account_indices.iter().flatten().chain(&step.input_account).chain(&step.output_account);
+        require!(indices.all(|&index| (index as usize) < available), ArbitrageError::MissingAccounts);
+        if step.output_account.is_none() {
+            count += 1;
+        }
+        if step.account_indices.is_none() {
+            count += dex_account_count(step)?;
+        }
+    }
+    Ok(count)
+}
+
+// Number of DEX-specific accounts each step consumes from remaining_accounts
+fn dex_account_count(step: &SwapStep) -> Result<usize> {
+    // A split leg consumes each portion's accounts back to back
+    if let Some(split) = &step.split {
+        return split.iter().try_fold(0, |total, leg| {
+            Ok(total + dex_account_count(&split_portion_step(step, leg))?)
+        });
+    }
+
+    // The remainder venue's accounts follow the primary's
+    let remainder_count = match step.remainder_dex {
+        Some(remainder_dex) => venue_account_count(remainder_dex, step)?,
+        None => 0,
+    };
+    Ok(venue_account_count(step.dex, step)? + remainder_count)
+}
+
+fn venue_account_count(dex: DexType, step: &SwapStep) -> Result<usize> {
+    Ok(match dex {
+        DexType::Jupiter => 0,
+        DexType::Raydium => RAYDIUM_ACCOUNT_COUNT,
+        DexType::Orca => ORCA_ACCOUNT_COUNT,
+        DexType::Meteora => METEORA_ACCOUNT_COUNT + meteora_bin_array_count(step)?,
+        DexType::Phoenix => PHOENIX_ACCOUNT_COUNT,
+        DexType::OpenBookV2 => OPENBOOK_ACCOUNT_COUNT,
+        DexType::Lifinity => LIFINITY_ACCOUNT_COUNT,
+        DexType::Saber => SABER_ACCOUNT_COUNT,
+        DexType::Crema => CREMA_ACCOUNT_COUNT + crema_tick_array_count(step)?,
+        DexType::Invariant => INVARIANT_ACCOUNT_COUNT + invariant_tick_count(step)?,
+        DexType::Custom => 1 + custom_account_count(step)?,
+    })
+}
+
+// Whoever owns the route's token accounts: the user's vault when one is passed, else the user
+fn trading_authority<'info>(ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>) -> AccountInfo<'info> {
+    match &ctx.accounts.vault {
+        Some(vault) => vault.to_account_info(),
+        None => ctx.accounts.user.to_account_info(),
+    }
+}
+
+// Runs `f` with the seeds the trading authority signs with (none for the user,
+// who signs the transaction itself)
+fn with_trader_seeds<'info, R>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    f: impl FnOnce(&[&[&[u8]]]) -> R,
+) -> R {
+    match &ctx.accounts.vault {
+        Some(vault) => {
+            let owner = ctx.accounts.user.key();
+            f(&[&[VAULT_SEED, owner.as_ref(), &[vault.bump]]])
+        }
+        None => f(&[]),
+    }
+}
+
+// Accounts referenced by key rather than position (oracles, pool reserves)
+fn find_remaining_account<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    key: Pubkey,
+) -> Option<&'info AccountInfo<'info>> {
+    ctx.remaining_accounts.iter().find(|info| info.key() == key)
+}
+
+fn check_pool_liquidity<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    liquidity: &PoolLiquidity,
+) -> Result<()> {
+    for reserve in &liquidity.reserves {
+        let info = find_remaining_account(ctx, *reserve).ok_or(ArbitrageError::MissingDexAccounts)?;
+        let amount = load_token_account(info)?.amount;
+        require!(amount >= liquidity.min_pool_liquidity, ArbitrageError::InsufficientLiquidity);
+    }
+    Ok(())
+}
+
+// Constant-product output for input_amount against the pool's current vault
+// balances: out = reserve_out * in / (reserve_in + in)
+fn live_reserve_output<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    reserves: &LiveReserves,
+    input_amount: u64,
+) -> Result<u64> {
+    let load = |key: Pubkey, mint: Pubkey| -> Result<u64> {
+        let info = find_remaining_account(ctx, key).ok_or(ArbitrageError::MissingDexAccounts)?;
+        let vault = load_token_account(info)?;
+        require_keys_eq!(vault.mint, mint, ArbitrageError::PoolMintMismatch);
+        Ok(vault.amount)
+    };
+    let reserve_in = load(reserves.input_vault, step.input_mint)?;
+    let reserve_out = load(reserves.output_vault, step.output_mint)?;
+    require!(reserve_in > 0 && reserve_out > 0, ArbitrageError::InsufficientLiquidity);
+
+    Ok(constant_product_output(reserve_in, reserve_out, input_amount)?)
+}
+
+fn load_token_account(info: &AccountInfo) -> Result<TokenAccount> {
+    require!(is_token_program(info.owner), ArbitrageError::InvalidTokenAccount);
+    let data = info.try_borrow_data()?;
+    TokenAccount::try_deserialize(&mut &data[..])
+}
+
+fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == token::ID || *program_id == token_2022::ID
+}
+
+// Spendable balance plus any Token-2022 transfer fee withheld on the account
+fn load_token_balance(info: &AccountInfo) -> Result<TokenBalance> {
+    require!(is_token_program(info.owner), ArbitrageError::InvalidTokenAccount);
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+    let withheld_fee = state
+        .get_extension::<TransferFeeAmount>()
+        .map(|fee| u64::from(fee.withheld_amount))
+        .unwrap_or(0);
+
+    Ok(TokenBalance {
+        amount: state.base.amount,
+        withheld_fee,
+    })
+}
+
+// 🔁 LEG EXECUTION
+//
+// Runs route[steps] starting from start_amount and returns what the last leg
+// delivered. trade_amount is what entered step 0, so compounded slippage is
+// measured from the start of the route even when resuming partway.
+#[allow(clippy::too_many_arguments)]
+fn execute_route_steps<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    route: &[SwapStep],
+    route_accounts: &RouteAccounts<'info>,
+    steps: Range<usize>,
+    start_amount: u64,
+    trade_amount: u64,
+    max_slippage_bps: u16,
+    max_total_slippage_bps: u16,
+    expected_profit_bps: u64,
+    dry_run: bool,
+) -> Result<u64> {
+    let mut current_amount = start_amount;
+    // Where the quotes say the amount should be by now, to measure compounded slippage
+    let mut projected_amount = route[..steps.start]
+        .iter()
+        .try_fold(trade_amount, |amount, step| project_step_output(amount, step.expected_rate))?;
+
+    // Every failure inside a leg is reported with the leg's index before it propagates
+    let mut run_step = |step_index: usize, step: &SwapStep| -> Result<()> {
+        trace_msg!(ctx.accounts.config, "Step {}: {} -> {}", step_index + 1, step.input_mint, step.output_mint);
+
+        // Bail out before a leg that would likely run out of compute halfway
+        let remaining_compute = sol_remaining_compute_units();
+        require!(
+            remaining_compute >= ctx.accounts.config.min_compute_per_leg,
+            ArbitrageError::ComputeExhausted
+        );
+
+        let accounts = StepAccounts {
+            input: match route_accounts.inputs[step_index] {
+                Some(input) => input,
+                None if step_index == 0 => ctx.accounts.user_token_account.as_ref(),
+                None => route_accounts.outputs[step_index - 1],
+            },
+            output: route_accounts.outputs[step_index],
+            dex: &route_accounts.dex[step_index],
+        };
+        
+        emit_pool_snapshot(ctx, step_index, step, false)?;
+
+        // Execute individual swap through Jupiter/DEX
+        let swap_result = execute_single_swap(
+            ctx,
+            step,
+            &accounts,
+            current_amount,
+            max_slippage_bps,
+            dry_run,
+        )
+        .inspect_err(|error| msg!("Step {} on {:?} failed: {}", step_index + 1, step.dex, error))?;
+        if ctx.accounts.config.meter_legs {
+            emit!(LegCompute {
+                step_index: step_index as u8,
+                units: compute_used_since(remaining_compute),
+            });
+        }
+        
+        trace_msg!(ctx.accounts.config, "Step {} output: {}", step_index + 1, swap_result.output_amount);
+        
+        // SAFETY: If any step fails, entire transaction reverts
+        if !swap_result.success {
+            emit!(ArbitrageAborted {
+                reason: AbortReason::SlippageExceeded,
+                realized_profit_bps: 0,
+                expected_profit_bps,
+            });
+            return err!(ArbitrageError::SwapFailed);
+        }
+
+        emit!(SwapStepExecuted {
+            step_index: step_index as u8,
+            dex: step.dex,
+            input_mint: step.input_mint,
+            output_mint: step.output_mint,
+            input_amount: current_amount,
+            output_amount: swap_result.output_amount,
+            slippage_bps: swap_result.slippage.bps,
+            slippage_direction: swap_result.slippage.direction,
+            transfer_fee: swap_result.transfer_fee,
+        });
+        emit_pool_snapshot(ctx, step_index, step, true)?;
+
+        current_amount = swap_result.output_amount;
+
+        // Legs each within their own bound can still add up to an unacceptable route
+        projected_amount = project_step_output(projected_amount, step.expected_rate)?;
+        let total_slippage = calculate_slippage(projected_amount, current_amount);
+        if total_slippage.direction == SlippageDirection::Unfavorable
+            && total_slippage.bps > max_total_slippage_bps
+        {
+            emit!(ArbitrageAborted {
+                reason: AbortReason::SlippageExceeded,
+                realized_profit_bps: 0,
+                expected_profit_bps,
+            });
+            return err!(ArbitrageError::SlippageExceeded);
+        }
+        Ok(())
+    };
+
+    for (step_index, step) in route.iter().enumerate().take(steps.end).skip(steps.start) {
+        run_step(step_index, step).inspect_err(|error| {
+            emit!(ArbitrageFailed {
+                step_index: step_index as u8,
+                error_code: error_code_number(error),
+            });
+        })?;
+    }
+    Ok(current_amount)
+}
+
+// 🧹 RESIDUAL TOKENS
+//
+// A finished route should pass straight through its intermediate accounts;
+// anything left in them is stuck in a token the user didn't start with.
+// Exempt: accounts that are user_token_account itself, and the input of a leg
+// that may keep part of what it was sent (see keeps_unspent_input).
+fn intermediate_balances<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    route: &[SwapStep],
+    route_accounts: &RouteAccounts<'info>,
+) -> Result<Vec<(&'info AccountInfo<'info>, u64)>> {
+    if !ctx.accounts.config.check_residual_tokens {
+        return Ok(Vec::new());
+    }
+    residual_checked_accounts(route, &route_accounts.outputs, ctx.accounts.user_token_account.key())
+        .into_iter()
+        .map(|info| Ok((info, load_token_account(info)?.amount)))
+        .collect()
+}
+
+// The intermediate outputs whose balance must be back where it started
+fn residual_checked_accounts<'a, 'info>(
+    route: &[SwapStep],
+    outputs: &[&'a AccountInfo<'info>],
+    start_account: Pubkey,
+) -> Vec<&'a AccountInfo<'info>> {
+    route
+        .iter()
+        .skip(1)
+        .zip(outputs)
+        .filter(|(next_step, info)| info.key() != start_account && !keeps_unspent_input(next_step))
+        .map(|(_, info)| *info)
+        .collect()
+}
+
+// An exact-output leg keeps the unspent remainder by design. An order-book
+// leg (primary, split portion or remainder venue) spends only whole lots and
+// its IOC order may fill in part, so dust can stay behind in its input.
+fn keeps_unspent_input(step: &SwapStep) -> bool {
+    let is_order_book = |dex: DexType| matches!(dex, DexType::Phoenix | DexType::OpenBookV2);
+    step.exact_output.is_some()
+        || is_order_book(step.dex)
+        || step.remainder_dex.is_some_and(is_order_book)
+        || step.split.iter().flatten().any(|leg| is_order_book(leg.dex))
+}
+
+fn require_no_residual_tokens(balances_before: &[(&AccountInfo, u64)]) -> Result<()> {
+    for (info, before) in balances_before {
+        let after = load_token_account(info)?.amount;
+        if after > *before {
+            msg!("{} holds {} more than before the route", info.key(), after - before);
+            return err!(ArbitrageError::ResidualTokens);
+        }
+    }
+    Ok(())
+}
+
+// With config.pool_snapshots on, logs the leg's pool vault balances (from
+// live_reserves) around the swap. Reading and emitting costs compute, hence opt-in.
+fn emit_pool_snapshot<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step_index: usize,
+    step: &SwapStep,
+    post_swap: bool,
+) -> Result<()> {
+    if !ctx.accounts.config.pool_snapshots {
+        return Ok(());
+    }
+    let Some(reserves) = &step.live_reserves else {
+        return Ok(());
+    };
+    let balance = |key: Pubkey| -> Result<u64> {
+        let info = find_remaining_account(ctx, key).ok_or(ArbitrageError::MissingDexAccounts)?;
+        Ok(load_token_account(info)?.amount)
+    };
+    emit!(PoolSnapshot {
+        step_index: step_index as u8,
+        reserve_a: balance(reserves.input_vault)?,
+        reserve_b: balance(reserves.output_vault)?,
+        post_swap,
+    });
+    Ok(())
+}
+
+// The numeric code a client sees for this error (Anchor's, or the raw program error's)
+fn error_code_number(error: &Error) -> u32 {
+    match error {
+        Error::AnchorError(error) => error.error_code_number,
+        Error::ProgramError(error) => u64::from(error.program_error.clone()) as u32,
+    }
+}
+
+// 🏗️ ATOMIC SWAP EXECUTION HELPER
+fn execute_single_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    max_slippage_bps: u16,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing swap: {} -> {}", step.input_mint, step.output_mint);
+    // A leg fed nothing would size min_output to 0 and "succeed", hiding a broken route
+    require!(input_amount > 0, ArbitrageError::ZeroStepInput);
+
+    // A client-computed output that disagrees with its own rate means one of them is wrong
+    if let Some(expected_output) = step.expected_output {
+        let projected = project_step_output(input_amount, step.expected_rate)?;
+        let tolerance_bps = step.slippage_bps.unwrap_or(max_slippage_bps);
+        require!(
+            calculate_slippage(projected, expected_output).bps <= tolerance_bps,
+            ArbitrageError::ExpectedOutputMismatch
+        );
+    }
+
+    match &step.split {
+        Some(split) => execute_split_swap(ctx, step, split, accounts, input_amount, max_slippage_bps, dry_run),
+        None => match step.remainder_dex {
+            Some(remainder_dex) => {
+                execute_with_remainder_venue(ctx, step, remainder_dex, accounts, input_amount, max_slippage_bps, dry_run)
+            }
+            None => execute_dex_swap(ctx, step, accounts, input_amount, max_slippage_bps, dry_run),
+        },
+    }
+}
+
+fn execute_dex_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    max_slippage_bps: u16,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    // SAFETY: never CPI into a program the registry doesn't know about
+    require!(
+        ctx.accounts.dex_registry.is_allowed(step.dex, step.program_id),
+        ArbitrageError::DexNotWhitelisted
+    );
+
+    // Per-step tolerance wins over the route-wide one, but never exceeds the venue's cap
+    let slippage_bps = step
+        .slippage_bps
+        .unwrap_or(max_slippage_bps)
+        .min(ctx.accounts.dex_registry.max_slippage_bps(step.dex, step.program_id));
+
+    // An oracle price, when given, replaces the client-supplied expected_rate.
+    // The slippage tolerance widens by the feed's confidence band, so a
+    // confident price holds the fill tight and an uncertain one lets it
+    // drift, up to the configured deviation bound.
+    // Failing that, live vault balances give a fresher quote than the rate.
+    let (quoted_output, tolerance_bps) = match (&step.oracle, &step.live_reserves) {
+        (Some(oracle), _) => {
+            let (output, confidence_bps) = oracle_quote_output(ctx, oracle, input_amount)?;
+            let tolerance_bps = slippage_bps
+                .saturating_add(confidence_bps)
+                .min(ctx.accounts.config.oracle_max_deviation_bps);
+            (output, tolerance_bps)
+        }
+        (None, Some(reserves)) => (live_reserve_output(ctx, step, reserves, input_amount)?, slippage_bps),
+        // Stable pools barely move near the peg; quote off their invariant, not a flat rate
+        (None, None) if step.dex == DexType::Saber => {
+            (saber_quote_output(accounts.dex, input_amount)?, slippage_bps)
+        }
+        (None, None) => (project_step_output(input_amount, step.expected_rate)?, slippage_bps),
+    };
+    // Of the quote and the client's precomputed output, size min_output from the stricter
+    let quoted_output = quoted_output.max(step.expected_output.unwrap_or(0));
+
+    // Take the venue's swap fee off the quote, then allow for slippage. Both
+    // round up: a floored bound would let the fill slip a unit further than allowed.
+    let fee_bps = ctx.accounts.dex_registry.fee_bps(step.dex, step.program_id);
+    let expected_after_fee = after_venue_fee(quoted_output, fee_bps)?;
+    // An exact-output leg must land precisely its target; input_amount becomes the spend cap
+    let min_output = match step.exact_output {
+        Some(amount_out) => amount_out,
+        None => checked_mul_div_rounded(expected_after_fee, 10000 - tolerance_bps as u64, 10000, Rounding::Ceil)?,
+    };
+    
+    // Thin pools slip catastrophically; refuse them before spending anything
+    if let Some(liquidity) = &step.liquidity {
+        check_pool_liquidity(ctx, liquidity)?;
+    }
+
+    let result = match step.dex {
+        DexType::Jupiter => execute_jupiter_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Raydium => execute_raydium_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Orca => execute_orca_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Meteora => execute_meteora_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Phoenix => execute_phoenix_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::OpenBookV2 => execute_openbook_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Lifinity => execute_lifinity_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Saber => execute_saber_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Crema => execute_crema_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Invariant => execute_invariant_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+        DexType::Custom => execute_custom_swap(ctx, step, accounts, input_amount, min_output, dry_run),
+    }?;
+
+    // A primary with a remainder venue reports the shortfall so the remainder can run
+    if step.oracle.is_some() && step.remainder_dex.is_none() {
+        require!(result.success, ArbitrageError::OracleDeviationExceeded);
+    }
+    Ok(result)
+}
+
+// 🔀 SPLIT LEGS
+//
+// The leg's input is divided by weight across the portions (the last portion
+// takes the rounding remainder) and their outputs are summed. Each portion is
+// executed as its own swap, so registry and slippage checks apply per venue.
+fn execute_split_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    split: &[SplitLeg],
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    max_slippage_bps: u16,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    let mut remaining_input = input_amount;
+    let mut remaining_dex_accounts = accounts.dex;
+    let mut combined = SwapResult {
+        success: true,
+        output_amount: 0,
+        slippage: calculate_slippage(0, 0),
+        transfer_fee: 0,
+    };
+
+    for (portion_index, leg) in split.iter().enumerate() {
+        let portion_step = split_portion_step(step, leg);
+        let portion_input = if portion_index == split.len() - 1 {
+            remaining_input
+        } else {
+            calculate_bps_share(input_amount, leg.weight_bps)?
+        };
+        remaining_input -= portion_input;
+        trace_msg!(ctx.accounts.config, "Split portion {:?}: {} ({}bps)", leg.dex, portion_input, leg.weight_bps);
+
+        let count = dex_account_count(&portion_step)?;
+        require!(remaining_dex_accounts.len() >= count, ArbitrageError::MissingDexAccounts);
+        let (portion_dex_accounts, rest) = remaining_dex_accounts.split_at(count);
+        remaining_dex_accounts = rest;
+
+        let portion_accounts = StepAccounts {
+            input: accounts.input,
+            output: accounts.output,
+            dex: portion_dex_accounts,
+        };
+        let result = execute_dex_swap(ctx, &portion_step, &portion_accounts, portion_input, max_slippage_bps, dry_run)?;
+
+        combined.success &= result.success;
+        combined.output_amount = combined
+            .output_amount
+            .checked_add(result.output_amount)
+            .ok_or(ArbitrageError::MathOverflow)?;
+        combined.transfer_fee = combined.transfer_fee.saturating_add(result.transfer_fee);
+    }
+
+    combined.slippage = calculate_slippage(
+        project_step_output(input_amount, step.expected_rate)?,
+        combined.output_amount.saturating_add(combined.transfer_fee),
+    );
+    Ok(combined)
+}
+
+// 🪂 REMAINDER VENUES
+//
+// Not a fallback for a failed leg: a CPI that errors aborts the transaction
+// outright, and input a primary has already spent can't be taken back to try
+// elsewhere. What a remainder venue does cover is a primary that returned
+// short with input left over, typically a partially filled order book order.
+// That unspent input is swapped on the remainder venue, whose program id comes
+// from the registry, and the two outputs are summed. A primary that spent all
+// its input and still under-delivered is not retried; the leg fails as before.
+fn execute_with_remainder_venue<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    remainder_dex: DexType,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    max_slippage_bps: u16,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    let primary_count = venue_account_count(step.dex, step)?;
+    require!(accounts.dex.len() >= primary_count, ArbitrageError::MissingDexAccounts);
+    let (primary_dex_accounts, remainder_dex_accounts) = accounts.dex.split_at(primary_count);
+
+    let input_before = load_token_account(accounts.input)?.amount;
+    let primary = execute_dex_swap(
+        ctx,
+        step,
+        &StepAccounts { input: accounts.input, output: accounts.output, dex: primary_dex_accounts },
+        input_amount,
+        max_slippage_bps,
+        dry_run,
+    )?;
+    if primary.success {
+        return Ok(primary);
+    }
+
+    let spent = input_before.saturating_sub(load_token_account(accounts.input)?.amount);
+    let remaining_input = input_amount.saturating_sub(spent);
+    trace_msg!(ctx.accounts.config, "Primary {:?} fell short; swapping the remaining {} on {:?}", step.dex, remaining_input, remainder_dex);
+    if remaining_input == 0 {
+        return Ok(primary);
+    }
+
+    let remainder_step = SwapStep {
+        dex: remainder_dex,
+        program_id: ctx
+            .accounts
+            .dex_registry
+            .program_id_for(remainder_dex)
+            .ok_or(ArbitrageError::DexNotWhitelisted)?,
+        account_indices: None,
+        remainder_dex: None,
+        live_reserves: None,
+        expected_output: None,
+        ..step.clone()
+    };
+    let remainder = execute_dex_swap(
+        ctx,
+        &remainder_step,
+        &StepAccounts { input: accounts.input, output: accounts.output, dex: remainder_dex_accounts },
+        remaining_input,
+        max_slippage_bps,
+        dry_run,
+    )?;
+
+    let output_amount = primary
+        .output_amount
+        .checked_add(remainder.output_amount)
+        .ok_or(ArbitrageError::MathOverflow)?;
+    let transfer_fee = primary.transfer_fee.saturating_add(remainder.transfer_fee);
+    Ok(SwapResult {
+        success: remainder.success,
+        output_amount,
+        slippage: calculate_slippage(
+            project_step_output(input_amount, step.expected_rate)?,
+            output_amount.saturating_add(transfer_fee),
+        ),
+        transfer_fee,
+    })
+}
+
+fn planned_swap_cpis(step: &SwapStep) -> usize {
+    let venues = step.split.as_ref().map_or(1, |split| split.len());
+    venues + step.remainder_dex.map_or(0, |_| 1)
+}
+
+// The step as seen by a single split portion: same mints and rate, its own venue
+fn split_portion_step(step: &SwapStep, leg: &SplitLeg) -> SwapStep {
+    SwapStep {
+        dex: leg.dex,
+        program_id: leg.program_id,
+        route_data: leg.route_data.clone(),
+        split: None,
+        account_indices: None,
+        remainder_dex: None,
+        live_reserves: None,
+        expected_output: None,
+        ..step.clone()
+    }
+}
+
+// 💸 SETTLEMENT
+fn collect_protocol_fee<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    protocol_fee: u64,
+) -> Result<()> {
+    if protocol_fee == 0 {
+        return Ok(());
+    }
+    with_trader_seeds(ctx, |signer_seeds| {
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: trading_authority(ctx),
+                },
+                signer_seeds,
+            ),
+            protocol_fee,
+            ctx.accounts.token_mint.decimals,
+        )
+    })
+}
+
+// The strategist's share goes to a token account owned by config.strategist
+fn pay_strategist<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    strategist_fee: u64,
+) -> Result<()> {
+    if strategist_fee == 0 {
+        return Ok(());
+    }
+    let Some(strategist_account) = &ctx.accounts.strategist_token_account else {
+        return err!(ArbitrageError::MissingStrategistAccount);
+    };
+    require!(
+        Some(strategist_account.owner) == ctx.accounts.config.strategist,
+        ArbitrageError::MissingStrategistAccount
+    );
+    with_trader_seeds(ctx, |signer_seeds| {
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: strategist_account.to_account_info(),
+                    authority: trading_authority(ctx),
+                },
+                signer_seeds,
+            ),
+            strategist_fee,
+            ctx.accounts.token_mint.decimals,
+        )
+    })?;
+    trace_msg!(ctx.accounts.config, "Paid {} to strategist {}", strategist_fee, strategist_account.owner);
+    Ok(())
+}
+
+// Profit stays in user_token_account unless a recipient was given
+fn pay_profit_recipient<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    profit: u64,
+) -> Result<()> {
+    let Some(recipient) = &ctx.accounts.profit_recipient else {
+        return Ok(());
+    };
+    if profit == 0 {
+        return Ok(());
+    }
+    with_trader_seeds(ctx, |signer_seeds| {
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: recipient.to_account_info(),
+                    authority: trading_authority(ctx),
+                },
+                signer_seeds,
+            ),
+            profit,
+            ctx.accounts.token_mint.decimals,
+        )
+    })?;
+    trace_msg!(ctx.accounts.config, "Paid {} profit to {}", profit, recipient.key());
+    Ok(())
+}
+
+fn pay_jito_tip<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    jito_tip_lamports: u64,
+) -> Result<()> {
+    if jito_tip_lamports == 0 {
+        return Ok(());
+    }
+    let tip_account = ctx
+        .accounts
+        .jito_tip_account
+        .as_ref()
+        .ok_or(ArbitrageError::MissingTipAccount)?;
+    system_program::transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: tip_account.to_account_info(),
+            },
+        ),
+        jito_tip_lamports,
+    )?;
+    trace_msg!(ctx.accounts.config, "Tipped {} lamports to {}", jito_tip_lamports, tip_account.key());
+    Ok(())
+}
+
+// 🌯 WRAPPED SOL
+const NATIVE_MINT: Pubkey = token::spl_token::native_mint::ID;
+
+// Creates the user's WSOL ATA for intermediate SOL legs when it doesn't exist.
+// Only accounts created here are returned, so a pre-existing WSOL account is
+// never closed out from under the user.
+fn create_temp_wsol_accounts<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    route: &[SwapStep],
+) -> Result<Vec<&'info AccountInfo<'info>>> {
+    let mut temp_accounts: Vec<&'info AccountInfo<'info>> = Vec::new();
+    let wsol_steps = route
+        .iter()
+        .enumerate()
+        .take(route.len().saturating_sub(1)) // the last leg pays into user_token_account
+        .filter(|(_, step)| step.output_mint == NATIVE_MINT);
+
+    for (step_index, _) in wsol_steps {
+        let native_mint = ctx.accounts.native_mint.as_ref().ok_or(ArbitrageError::MissingWrapAccounts)?;
+        let associated_token_program = ctx
+            .accounts
+            .associated_token_program
+            .as_ref()
+            .ok_or(ArbitrageError::MissingWrapAccounts)?;
+        require_keys_eq!(ctx.accounts.token_program.key(), token::ID, ArbitrageError::InvalidWrapSetup);
+
+        let wsol_account = ctx
+            .remaining_accounts
+            .get(output_account_index(route, step_index))
+            .ok_or(ArbitrageError::MissingTokenAccount)?;
+        let expected = get_associated_token_address(&ctx.accounts.user.key(), &NATIVE_MINT);
+        require_keys_eq!(wsol_account.key(), expected, ArbitrageError::TokenAccountMismatch);
+
+        if wsol_account.data_is_empty() {
+            associated_token::create_idempotent(CpiContext::new(
+                associated_token_program.to_account_info(),
+                associated_token::Create {
+                    payer: ctx.accounts.user.to_account_info(),
+                    associated_token: wsol_account.clone(),
+                    authority: ctx.accounts.user.to_account_info(),
+                    mint: native_mint.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+            ))?;
+            trace_msg!(ctx.accounts.config, "Created temporary WSOL account {}", wsol_account.key());
+            temp_accounts.push(wsol_account);
+        }
+    }
+
+    Ok(temp_accounts)
+}
+
+// Tops user_token_account (a WSOL account) up to `target` with the user's lamports
+fn wrap_starting_sol<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    target: u64,
+) -> Result<()> {
+    let shortfall = target.saturating_sub(ctx.accounts.user_token_account.amount);
+    if shortfall == 0 {
+        return Ok(());
+    }
+    trace_msg!(ctx.accounts.config, "Wrapping {} lamports", shortfall);
+
+    system_program::transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+            },
+        ),
+        shortfall,
+    )?;
+    token_interface::sync_native(CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        SyncNative {
+            account: ctx.accounts.user_token_account.to_account_info(),
+        },
+    ))
+}
+
+// ⚡ FLASH LOANS (Solend)
+//
+// Solend rejects flash borrows and repays made through CPI, so the client
+// brackets the route with them as top-level instructions:
+//   i-1  FlashBorrowReserveLiquidity(flash_loan_amount) into user_token_account
+//   i    execute_arbitrage_route
+//   i+1  FlashRepayReserveLiquidity(flash_loan_amount, i-1) from user_token_account
+// The route finds both through the instructions sysvar, its one flash loan
+// account, and refuses to trade unless they match. The lender's fee is taken
+// by the repay, after the route has run, so callers cover it with
+// fee_reserve_amount.
+const FLASH_LOAN_ACCOUNT_COUNT: usize = 1;
+const SOLEND_PROGRAM_ID: Pubkey = pubkey!("So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo");
+const SOLEND_FLASH_BORROW_TAG: u8 = 19;
+const SOLEND_FLASH_REPAY_TAG: u8 = 20;
+// Positions in each instruction's account list
+const SOLEND_BORROW_DESTINATION_INDEX: usize = 1;
+const SOLEND_BORROW_RESERVE_INDEX: usize = 2;
+const SOLEND_REPAY_SOURCE_INDEX: usize = 0;
+const SOLEND_REPAY_RESERVE_INDEX: usize = 4;
+
+fn verify_flash_loan<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    flash_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    let [instructions_sysvar] = flash_accounts else {
+        return err!(ArbitrageError::MissingFlashLoanAccounts);
+    };
+    require_keys_eq!(instructions_sysvar.key(), instructions::ID, ArbitrageError::MissingFlashLoanAccounts);
+
+    let current_index = load_current_index_checked(instructions_sysvar)? as usize;
+    let borrow_index = current_index.checked_sub(1).ok_or(ArbitrageError::FlashLoanMismatch)?;
+    let borrow = load_instruction_at_checked(borrow_index, instructions_sysvar)
+        .map_err(|_| ArbitrageError::FlashLoanMismatch)?;
+    let repay = load_instruction_at_checked(current_index + 1, instructions_sysvar)
+        .map_err(|_| ArbitrageError::FlashLoanMismatch)?;
+
+    let user_token_account = Some(ctx.accounts.user_token_account.key());
+    let account_at = |instruction: &Instruction, index: usize| instruction.accounts.get(index).map(|meta| meta.pubkey);
+    require!(
+        borrow.program_id == SOLEND_PROGRAM_ID
+            && flash_instruction_amount(&borrow, SOLEND_FLASH_BORROW_TAG) == Some(amount)
+            && account_at(&borrow, SOLEND_BORROW_DESTINATION_INDEX) == user_token_account,
+        ArbitrageError::FlashLoanMismatch
+    );
+    // The repay has to settle this very borrow, out of the account it went into
+    require!(
+        repay.program_id == SOLEND_PROGRAM_ID
+            && flash_instruction_amount(&repay, SOLEND_FLASH_REPAY_TAG) == Some(amount)
+            && repay.data.get(9).map(|&index| index as usize) == Some(borrow_index)
+            && account_at(&repay, SOLEND_REPAY_SOURCE_INDEX) == user_token_account
+            && account_at(&repay, SOLEND_REPAY_RESERVE_INDEX) == account_at(&borrow, SOLEND_BORROW_RESERVE_INDEX),
+        ArbitrageError::FlashLoanMismatch
+    );
+    trace_msg!(ctx.accounts.config, "Flash loan of {} bracketed by instructions {} and {}", amount, borrow_index, current_index + 1);
+    Ok(())
+}
+
+// The liquidity amount of a flash borrow or repay: tag byte, then a u64
+fn flash_instruction_amount(instruction: &Instruction, tag: u8) -> Option<u64> {
+    let (&instruction_tag, data) = instruction.data.split_first()?;
+    if instruction_tag != tag {
+        return None;
+    }
+    Some(u64::from_le_bytes(data.get(..8)?.try_into().ok()?))
+}
+
+// 📡 PRICE ORACLES (Pyth)
+//
+// A leg's oracle is a fully verified Pyth PriceUpdateV2 account, found among
+// remaining_accounts by key. The feed prices one unit of its base token in its
+// quote token; `inverted` marks legs that sell the quote for the base.
+const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+const PYTH_PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+const PYTH_VERIFICATION_LEVEL_OFFSET: usize = 40;
+const PYTH_VERIFICATION_FULL: u8 = 1;
+const PYTH_PRICE_OFFSET: usize = 73; // after the 1-byte Full tag and 32-byte feed id
+const PYTH_CONF_OFFSET: usize = 81;
+const PYTH_EXPONENT_OFFSET: usize = 89;
+const PYTH_PUBLISH_TIME_OFFSET: usize = 93;
+const PYTH_POSTED_SLOT_OFFSET: usize = 125; // after the message's prev publish time and EMA fields
+const USD_DECIMALS: u8 = 6; // profit_usd is reported in micro-dollars, like USDC
+
+struct OraclePrice {
+    price: u64,
+    conf: u64,
+    exponent: i32,
+    publish_time: i64,
+    posted_slot: u64,
+}
+
+fn read_pyth_price(info: &AccountInfo) -> Result<OraclePrice> {
+    require_keys_eq!(*info.owner, PYTH_RECEIVER_PROGRAM_ID, ArbitrageError::InvalidOracle);
+    let data = info.try_borrow_data()?;
+    require!(data.len() >= PYTH_POSTED_SLOT_OFFSET + 8, ArbitrageError::InvalidOracle);
+    require!(data[..8] == PYTH_PRICE_UPDATE_DISCRIMINATOR, ArbitrageError::InvalidOracle);
+    // Partially verified updates have a different layout and weaker guarantees
+    require!(
+        data[PYTH_VERIFICATION_LEVEL_OFFSET] == PYTH_VERIFICATION_FULL,
+        ArbitrageError::InvalidOracle
+    );
+
+    let read_i64 = |offset: usize| i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let price = read_i64(PYTH_PRICE_OFFSET);
+    let exponent = i32::from_le_bytes(data[PYTH_EXPONENT_OFFSET..PYTH_EXPONENT_OFFSET + 4].try_into().unwrap());
+    require!(price > 0, ArbitrageError::InvalidOracle);
+
+    Ok(OraclePrice {
+        price: price as u64,
+        conf: read_i64(PYTH_CONF_OFFSET) as u64,
+        exponent,
+        publish_time: read_i64(PYTH_PUBLISH_TIME_OFFSET),
+        posted_slot: read_i64(PYTH_POSTED_SLOT_OFFSET) as u64,
+    })
+}
+
+// What input_amount is worth in the leg's output token at the oracle mid-price,
+// alongside the feed's confidence band in bps of that price
+fn oracle_quote_output<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    oracle: &StepOracle,
+    input_amount: u64,
+) -> Result<(u64, u16)> {
+    let info = find_remaining_account(ctx, oracle.price_account).ok_or(ArbitrageError::InvalidOracle)?;
+    convert_at_oracle_price(
+        ctx,
+        info,
+        oracle.inverted,
+        oracle.input_decimals,
+        oracle.output_decimals,
+        input_amount,
+    )
+}
+
+// Converts between a feed's base and quote tokens, rejecting stale or
+// low-confidence prices. Also returns the confidence band in bps of price.
+fn convert_at_oracle_price<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    info: &AccountInfo,
+    inverted: bool,
+    input_decimals: u8,
+    output_decimals: u8,
+    input_amount: u64,
+) -> Result<(u64, u16)> {
+    let price = read_pyth_price(info)?;
+
+    // Wall-clock age alone can be gamed by reposting an old price; bound the slot age too
+    let clock = Clock::get()?;
+    let age = clock.unix_timestamp.saturating_sub(price.publish_time);
+    require!(age <= ctx.accounts.config.oracle_max_age_secs as i64, ArbitrageError::StaleOracle);
+    let slot_age = clock.slot.saturating_sub(price.posted_slot);
+    require!(
+        slot_age <= ctx.accounts.config.max_oracle_staleness_slots,
+        ArbitrageError::StaleOracle
+    );
+
+    // conf shares price's exponent, so their ratio is scale-free. Round up so a
+    // band just past the limit can't floor its way under it.
+    let confidence_bps = checked_mul_div_rounded(price.conf, 10000, price.price, Rounding::Ceil)?;
+    require!(
+        confidence_bps <= ctx.accounts.config.max_oracle_confidence_bps as u64,
+        ArbitrageError::OracleConfidenceTooWide
+    );
+    let confidence_bps = confidence_bps as u16;
+
+    // Scale by the feed exponent and the decimals gap, multiplying or dividing by the price
+    let decimals_gap = output_decimals as i32 - input_decimals as i32;
+    let (scale, numerator, denominator) = if inverted {
+        (decimals_gap - price.exponent, input_amount as u128, price.price as u128)
+    } else {
+        (
+            decimals_gap + price.exponent,
+            (input_amount as u128).checked_mul(price.price as u128).ok_or(ArbitrageError::MathOverflow)?,
+            1,
+        )
+    };
+    let power = 10u128.checked_pow(scale.unsigned_abs()).ok_or(ArbitrageError::MathOverflow)?;
+    let output = if scale >= 0 {
+        numerator.checked_mul(power).ok_or(ArbitrageError::MathOverflow)? / denominator
+    } else {
+        numerator / denominator.checked_mul(power).ok_or(ArbitrageError::MathOverflow)?
+    };
+    let output = u64::try_from(output).map_err(|_| ArbitrageError::MathOverflow)?;
+    Ok((output, confidence_bps))
+}
+
+// Reporting only: a missing or unreadable feed reports 0 rather than failing a profitable run
+fn profit_in_usd<'info>(ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>, profit: u64) -> u64 {
+    let Some(feed) = &ctx.accounts.usd_price_feed else {
+        return 0;
+    };
+    let decimals = ctx.accounts.token_mint.decimals;
+    convert_at_oracle_price(ctx, feed, false, decimals, USD_DECIMALS, profit)
+        .map(|(usd, _)| usd)
+        .unwrap_or_else(|error| {
+            msg!("USD price unavailable: {}", error);
+            0
+        })
+}
+
+// 🪐 JUPITER INTEGRATION
+fn execute_jupiter_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Jupiter swap");
+    
+    // Create Jupiter swap instruction
+    let jupiter_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: vec![
+            AccountMeta::new(accounts.input.key(), false),
+            AccountMeta::new(accounts.output.key(), false),
+            AccountMeta::new_readonly(trading_authority(ctx).key(), true),
+        ],
+        data: create_jupiter_swap_data(input_amount, min_output, step.route_data.clone()),
+    };
+
+    if dry_run {
+        return plan_swap(&jupiter_instruction, step, input_amount);
+    }
+
+    // Execute the swap through CPI
+    measured_swap(
+        ctx,
+        step,
+        accounts,
+        &jupiter_instruction,
+        &[
+            accounts.input.clone(),
+            accounts.output.clone(),
+            trading_authority(ctx),
+        ],
+        input_amount,
+        min_output,
+    )
+}
+
+// 🌊 RAYDIUM INTEGRATION
+//
+// DEX accounts for an AMM v4 swap_base_in, in order:
+//   raydium program, then
+//   0 token program     5 pool coin vault   10 serum asks
+//   1 amm               6 pool pc vault     11 serum event queue
+//   2 amm authority     7 serum program     12 serum coin vault
+//   3 amm open orders   8 serum market      13 serum pc vault
+//   4 amm target orders 9 serum bids        14 serum vault signer
+// The user's source, destination and owner are appended by the helper.
+const RAYDIUM_ACCOUNT_COUNT: usize = 16;
+const RAYDIUM_SWAP_BASE_IN: u8 = 9;
+const RAYDIUM_SWAP_BASE_OUT: u8 = 11;
+
+fn execute_raydium_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Raydium swap");
+
+    let (program, pool_accounts) = accounts.dex.split_first().ok_or(ArbitrageError::MissingDexAccounts)?;
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    let trader = trading_authority(ctx);
+
+    // Pool and market accounts first, then the user's source/destination/owner
+    let mut metas = Vec::with_capacity(pool_accounts.len() + 3);
+    let mut infos = Vec::with_capacity(pool_accounts.len() + 4);
+    for (index, info) in pool_accounts.iter().enumerate() {
+        // token program, amm authority, serum program and vault signer are
+        // read-only; everything else is written by the AMM
+        let meta = match index {
+            0 | 2 | 7 | 14 => AccountMeta::new_readonly(info.key(), false),
+            _ => AccountMeta::new(info.key(), false),
+        };
+        metas.push(meta);
+        infos.push(info.clone());
+    }
+    metas.push(AccountMeta::new(accounts.input.key(), false));
+    metas.push(AccountMeta::new(accounts.output.key(), false));
+    metas.push(AccountMeta::new_readonly(trader.key(), true));
+    infos.push(accounts.input.clone());
+    infos.push(accounts.output.clone());
+    infos.push(trader);
+    infos.push(program.clone());
+
+    let raydium_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: metas,
+        data: match step.exact_output {
+            Some(amount_out) => create_raydium_swap_base_out_data(input_amount, amount_out),
+            None => create_raydium_swap_data(input_amount, min_output),
+        },
+    };
+
+    if dry_run {
+        return plan_swap(&raydium_instruction, step, input_amount);
+    }
+
+    measured_swap(ctx, step, accounts, &raydium_instruction, &infos, input_amount, min_output)
+}
+
+// 🐋 ORCA INTEGRATION
+//
+// DEX accounts for a Whirlpool swap, in order:
+//   0 whirlpool program   3 token vault a    6 tick array 1
+//   1 token program       4 token vault b    7 tick array 2
+//   2 whirlpool           5 tick array 0     8 oracle
+// The user's token accounts are slotted into the a/b positions according to
+// the swap direction, which is read from the whirlpool's mints.
+const ORCA_ACCOUNT_COUNT: usize = 9;
+const WHIRLPOOL_TOKEN_MINT_A_OFFSET: usize = 101;
+const WHIRLPOOL_TOKEN_MINT_B_OFFSET: usize = 181;
+const WHIRLPOOL_TICK_SPACING_OFFSET: usize = 41;
+const WHIRLPOOL_TICK_CURRENT_INDEX_OFFSET: usize = 81;
+const TICK_ARRAY_SIZE: i32 = 88;
+const TICK_ARRAY_START_TICK_OFFSET: usize = 8;
+const TICK_ARRAY_WHIRLPOOL_OFFSET: usize = 9956;
+const WHIRLPOOL_MIN_SQRT_PRICE: u128 = 4295048016;
+const WHIRLPOOL_MAX_SQRT_PRICE: u128 = 79226673515401279992447053055;
+
+fn execute_orca_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Orca swap");
+
+    let [program, token_program, whirlpool, vault_a, vault_b, tick_array_0, tick_array_1, tick_array_2, oracle] =
+        accounts.dex
+    else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    // a_to_b when we're selling the whirlpool's token A
+    let (mint_a, mint_b) = read_whirlpool_mints(whirlpool)?;
+    let a_to_b = if step.input_mint == mint_a && step.output_mint == mint_b {
+        true
+    } else if step.input_mint == mint_b && step.output_mint == mint_a {
+        false
+    } else {
+        return err!(ArbitrageError::PoolMintMismatch);
+    };
+    validate_whirlpool_tick_arrays(whirlpool, a_to_b, [tick_array_0, tick_array_1, tick_array_2])?;
+    let (owner_a, owner_b) = if a_to_b {
+        (accounts.input, accounts.output)
+    } else {
+        (accounts.output, accounts.input)
+    };
+
+    let trader = trading_authority(ctx);
+    let orca_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(token_program.key(), false),
+            AccountMeta::new_readonly(trader.key(), true),
+            AccountMeta::new(whirlpool.key(), false),
+            AccountMeta::new(owner_a.key(), false),
+            AccountMeta::new(vault_a.key(), false),
+            AccountMeta::new(owner_b.key(), false),
+            AccountMeta::new(vault_b.key(), false),
+            AccountMeta::new(tick_array_0.key(), false),
+            AccountMeta::new(tick_array_1.key(), false),
+            AccountMeta::new(tick_array_2.key(), false),
+            AccountMeta::new(oracle.key(), false),
+        ],
+        data: match step.exact_output {
+            Some(amount_out) => create_whirlpool_swap_data(amount_out, input_amount, false, a_to_b),
+            None => create_whirlpool_swap_data(input_amount, min_output, true, a_to_b),
+        },
+    };
+
+    if dry_run {
+        return plan_swap(&orca_instruction, step, input_amount);
+    }
+
+    let result = measured_swap(
+        ctx,
+        step,
+        accounts,
+        &orca_instruction,
+        &[
+            token_program.clone(),
+            trader,
+            whirlpool.clone(),
+            owner_a.clone(),
+            vault_a.clone(),
+            owner_b.clone(),
+            vault_b.clone(),
+            tick_array_0.clone(),
+            tick_array_1.clone(),
+            tick_array_2.clone(),
+            oracle.clone(),
+            program.clone(),
+        ],
+        input_amount,
+        min_output,
+    )?;
+
+    // Whirlpool enforces the threshold too, but never trust the callee
+    require!(result.success, ArbitrageError::SlippageExceeded);
+    Ok(result)
+}
+
+fn read_whirlpool_mints(whirlpool: &AccountInfo) -> Result<(Pubkey, Pubkey)> {
+    let data = whirlpool.try_borrow_data()?;
+    require!(
+        data.len() >= WHIRLPOOL_TOKEN_MINT_B_OFFSET + 32,
+        ArbitrageError::InvalidPoolAccount
+    );
+    let read_key = |offset: usize| {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&data[offset..offset + 32]);
+        Pubkey::new_from_array(key)
+    };
+    Ok((read_key(WHIRLPOOL_TOKEN_MINT_A_OFFSET), read_key(WHIRLPOOL_TOKEN_MINT_B_OFFSET)))
+}
+
+/// Checks that the tick arrays belong to the whirlpool and that the first one
+/// covers the current tick. Wrong arrays make the swap fill nothing instead of
+/// failing, so catch them before the CPI.
+fn validate_whirlpool_tick_arrays(
+    whirlpool: &AccountInfo,
+    a_to_b: bool,
+    tick_arrays: [&AccountInfo; 3],
+) -> Result<()> {
+    let (tick_spacing, tick_current) = {
+        let data = whirlpool.try_borrow_data()?;
+        require!(
+            data.len() >= WHIRLPOOL_TICK_CURRENT_INDEX_OFFSET + 4,
+            ArbitrageError::InvalidPoolAccount
+        );
+        let spacing = u16::from_le_bytes(
+            data[WHIRLPOOL_TICK_SPACING_OFFSET..WHIRLPOOL_TICK_SPACING_OFFSET + 2].try_into().unwrap(),
+        );
+        let current = i32::from_le_bytes(
+            data[WHIRLPOOL_TICK_CURRENT_INDEX_OFFSET..WHIRLPOOL_TICK_CURRENT_INDEX_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        );
+        (spacing as i32, current)
+    };
+    require!(tick_spacing > 0, ArbitrageError::InvalidPoolAccount);
+
+    let mut starts = [0i32; 3];
+    for (start, tick_array) in starts.iter_mut().zip(tick_arrays) {
+        let data = tick_array.try_borrow_data()?;
+        require!(
+            data.len() >= TICK_ARRAY_WHIRLPOOL_OFFSET + 32,
+            ArbitrageError::InvalidTickArrays
+        );
+        require!(
+            data[TICK_ARRAY_WHIRLPOOL_OFFSET..TICK_ARRAY_WHIRLPOOL_OFFSET + 32] == whirlpool.key().to_bytes(),
+            ArbitrageError::InvalidTickArrays
+        );
+        *start = i32::from_le_bytes(
+            data[TICK_ARRAY_START_TICK_OFFSET..TICK_ARRAY_START_TICK_OFFSET + 4].try_into().unwrap(),
+        );
+    }
+
+    // Selling B moves the price up; the whirlpool program lets the first array
+    // start one tick spacing ahead so a swap sitting on a boundary can cross it
+    let span = tick_spacing * TICK_ARRAY_SIZE;
+    let covers = |tick: i32| starts[0] <= tick && tick < starts[0] + span;
+    require!(
+        covers(tick_current) || (!a_to_b && covers(tick_current + tick_spacing)),
+        ArbitrageError::InvalidTickArrays
+    );
+    Ok(())
+}
+
+// ☄️ METEORA DLMM INTEGRATION
+//
+// DEX accounts for a DLMM swap, in order:
+//   0 dlmm program                  6 token y mint     12.. bin arrays
+//   1 lb pair                       7 oracle
+//   2 bin array bitmap extension    8 host fee in
+//   3 reserve x                     9 token x program
+//   4 reserve y                     10 token y program
+//   5 token x mint                  11 event authority
+// Optional accounts (bitmap extension, host fee) are passed as the DLMM
+// program itself when unused. route_data[0] holds the number of bin arrays.
+const METEORA_ACCOUNT_COUNT: usize = 12;
+const LB_PAIR_ACTIVE_ID_OFFSET: usize = 76;
+const BIN_ARRAY_INDEX_OFFSET: usize = 8;
+const BIN_ARRAY_LB_PAIR_OFFSET: usize = 24;
+const MAX_BIN_PER_ARRAY: i64 = 70;
+
+fn meteora_bin_array_count(step: &SwapStep) -> Result<usize> {
+    let count = *step.route_data.first().ok_or(ArbitrageError::InvalidRouteData)?;
+    require!(count > 0, ArbitrageError::InvalidRouteData);
+    Ok(count as usize)
+}
+
+/// Checks that the bin arrays belong to the pair and that the first one holds
+/// the active bin, where the swap starts filling.
+fn validate_dlmm_bin_arrays(lb_pair: &AccountInfo, bin_arrays: &[AccountInfo]) -> Result<()> {
+    let active_id = {
+        let data = lb_pair.try_borrow_data()?;
+        require!(
+            data.len() >= LB_PAIR_ACTIVE_ID_OFFSET + 4,
+            ArbitrageError::InvalidPoolAccount
+        );
+        i32::from_le_bytes(data[LB_PAIR_ACTIVE_ID_OFFSET..LB_PAIR_ACTIVE_ID_OFFSET + 4].try_into().unwrap())
+    };
+
+    for (i, bin_array) in bin_arrays.iter().enumerate() {
+        let data = bin_array.try_borrow_data()?;
+        require!(
+            data.len() >= BIN_ARRAY_LB_PAIR_OFFSET + 32,
+            ArbitrageError::InvalidTickArrays
+        );
+        require!(
+            data[BIN_ARRAY_LB_PAIR_OFFSET..BIN_ARRAY_LB_PAIR_OFFSET + 32] == lb_pair.key().to_bytes(),
+            ArbitrageError::InvalidTickArrays
+        );
+        if i == 0 {
+            let index = i64::from_le_bytes(
+                data[BIN_ARRAY_INDEX_OFFSET..BIN_ARRAY_INDEX_OFFSET + 8].try_into().unwrap(),
+            );
+            let first_bin = index * MAX_BIN_PER_ARRAY;
+            let active = active_id as i64;
+            require!(
+                first_bin <= active && active < first_bin + MAX_BIN_PER_ARRAY,
+                ArbitrageError::InvalidTickArrays
+            );
+        }
+    }
+    Ok(())
+}
+
+fn execute_meteora_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Meteora swap");
+
+    require!(accounts.dex.len() > METEORA_ACCOUNT_COUNT, ArbitrageError::MissingDexAccounts);
+    let (fixed, bin_arrays) = accounts.dex.split_at(METEORA_ACCOUNT_COUNT);
+    let [program, lb_pair, bitmap_extension, reserve_x, reserve_y, token_x_mint, token_y_mint, oracle, host_fee_in, token_x_program, token_y_program, event_authority] =
+        fixed
+    else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+    validate_dlmm_bin_arrays(lb_pair, bin_arrays)?;
+
+    let trader = trading_authority(ctx);
+
+    // Unused optional accounts are passed as the program id, read-only
+    let optional_meta = |info: &AccountInfo| {
+        if info.key() == step.program_id {
+            AccountMeta::new_readonly(info.key(), false)
+        } else {
+            AccountMeta::new(info.key(), false)
+        }
+    };
+
+    let mut metas = vec![
+        AccountMeta::new(lb_pair.key(), false),
+        optional_meta(bitmap_extension),
+        AccountMeta::new(reserve_x.key(), false),
+        AccountMeta::new(reserve_y.key(), false),
+        AccountMeta::new(accounts.input.key(), false),
+        AccountMeta::new(accounts.output.key(), false),
+        AccountMeta::new_readonly(token_x_mint.key(), false),
+        AccountMeta::new_readonly(token_y_mint.key(), false),
+        AccountMeta::new(oracle.key(), false),
+        optional_meta(host_fee_in),
+        AccountMeta::new_readonly(trader.key(), true),
+        AccountMeta::new_readonly(token_x_program.key(), false),
+        AccountMeta::new_readonly(token_y_program.key(), false),
+        AccountMeta::new_readonly(event_authority.key(), false),
+        AccountMeta::new_readonly(program.key(), false),
+    ];
+    metas.extend(bin_arrays.iter().map(|info| AccountMeta::new(info.key(), false)));
+
+    let mut infos = vec![
+        lb_pair.clone(),
+        bitmap_extension.clone(),
+        reserve_x.clone(),
+        reserve_y.clone(),
+        accounts.input.clone(),
+        accounts.output.clone(),
+        token_x_mint.clone(),
+        token_y_mint.clone(),
+        oracle.clone(),
+        host_fee_in.clone(),
+        trader,
+        token_x_program.clone(),
+        token_y_program.clone(),
+        event_authority.clone(),
+        program.clone(),
+    ];
+    infos.extend(bin_arrays.iter().cloned());
+
+    let meteora_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: metas,
+        data: create_meteora_swap_data(input_amount, min_output),
+    };
+
+    if dry_run {
+        return plan_swap(&meteora_instruction, step, input_amount);
+    }
+
+    measured_swap(ctx, step, accounts, &meteora_instruction, &infos, input_amount, min_output)
+}
+
+// 🔥 PHOENIX INTEGRATION
+//
+// Phoenix is an order book, so a leg is an immediate-or-cancel order rather
+// than an AMM swap. DEX accounts, in order:
+//   0 phoenix program   2 market       4 quote vault
+//   1 log authority     3 base vault   5 token program
+// min_output, scaled to the whole lots spent, becomes the order's minimum
+// fill in lots of the output token.
+const PHOENIX_ACCOUNT_COUNT: usize = 6;
+const PHOENIX_SWAP_TAG: u8 = 0;
+const PHOENIX_BASE_MINT_OFFSET: usize = 48;
+const PHOENIX_BASE_LOT_SIZE_OFFSET: usize = 112;
+const PHOENIX_QUOTE_MINT_OFFSET: usize = 128;
+const PHOENIX_QUOTE_LOT_SIZE_OFFSET: usize = 192;
+
+// Mints and lot sizes of an order-book market, shared by Phoenix and OpenBook
+struct OrderBookHeader {
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+    base_lot_size: u64,
+    quote_lot_size: u64,
+}
+
+// The whole-lot part of a leg's input an order can spend, and min_output
+// scaled down to it. min_output was sized for the full input, so without
+// scaling a complete fill of an unaligned input would fall short of it.
+struct LotAligned {
+    lots: u64,
+    input_amount: u64,
+    min_output: u64,
+}
+
+fn lot_aligned(input_amount: u64, lot_size: u64, min_output: u64) -> Result<LotAligned> {
+    let lots = input_amount / lot_size;
+    require!(lots > 0, ArbitrageError::InvalidRouteData);
+    let aligned_input = lots * lot_size;
+    Ok(LotAligned {
+        lots,
+        input_amount: aligned_input,
+        min_output: checked_mul_div_rounded(min_output, aligned_input, input_amount, Rounding::Ceil)?,
+    })
+}
+
+fn execute_phoenix_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Phoenix swap");
+
+    let [program, log_authority, market, base_vault, quote_vault, token_program] = accounts.dex else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    // Selling base is an ask sized in base lots; buying base is a bid sized in quote lots
+    let header = read_phoenix_market_header(market)?;
+    let is_ask = if step.input_mint == header.base_mint && step.output_mint == header.quote_mint {
+        true
+    } else if step.input_mint == header.quote_mint && step.output_mint == header.base_mint {
+        false
+    } else {
+        return err!(ArbitrageError::PoolMintMismatch);
+    };
+    let (base_account, quote_account) = if is_ask {
+        (accounts.input, accounts.output)
+    } else {
+        (accounts.output, accounts.input)
+    };
+    let aligned = lot_aligned(
+        input_amount,
+        if is_ask { header.base_lot_size } else { header.quote_lot_size },
+        min_output,
+    )?;
+    let order = if is_ask {
+        PhoenixOrder {
+            is_ask,
+            num_base_lots: aligned.lots,
+            num_quote_lots: 0,
+            min_base_lots_to_fill: 0,
+            min_quote_lots_to_fill: aligned.min_output.div_ceil(header.quote_lot_size),
+        }
+    } else {
+        PhoenixOrder {
+            is_ask,
+            num_base_lots: 0,
+            num_quote_lots: aligned.lots,
+            min_base_lots_to_fill: aligned.min_output.div_ceil(header.base_lot_size),
+            min_quote_lots_to_fill: 0,
+        }
+    };
+
+    let trader = trading_authority(ctx);
+    let phoenix_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(program.key(), false),
+            AccountMeta::new_readonly(log_authority.key(), false),
+            AccountMeta::new(market.key(), false),
+            AccountMeta::new_readonly(trader.key(), true),
+            AccountMeta::new(base_account.key(), false),
+            AccountMeta::new(quote_account.key(), false),
+            AccountMeta::new(base_vault.key(), false),
+            AccountMeta::new(quote_vault.key(), false),
+            AccountMeta::new_readonly(token_program.key(), false),
+        ],
+        data: create_phoenix_swap_data(&order),
+    };
+
+    if dry_run {
+        return plan_swap(&phoenix_instruction, step, input_amount);
+    }
+
+    // A partial fill leaves unspent input behind for a remainder venue to pick up
+    measured_swap(
+        ctx,
+        step,
+        accounts,
+        &phoenix_instruction,
+        &[
+            program.clone(),
+            log_authority.clone(),
+            market.clone(),
+            trader,
+            base_account.clone(),
+            quote_account.clone(),
+            base_vault.clone(),
+            quote_vault.clone(),
+            token_program.clone(),
+        ],
+        aligned.input_amount,
+        aligned.min_output,
+    )
+}
+
+fn read_phoenix_market_header(market: &AccountInfo) -> Result<OrderBookHeader> {
+    let data = market.try_borrow_data()?;
+    require!(
+        data.len() >= PHOENIX_QUOTE_LOT_SIZE_OFFSET + 8,
+        ArbitrageError::InvalidPoolAccount
+    );
+    let read_key = |offset: usize| {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&data[offset..offset + 32]);
+        Pubkey::new_from_array(key)
+    };
+    let read_u64 = |offset: usize| {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(&data[offset..offset + 8]);
+        u64::from_le_bytes(value)
+    };
+
+    let header = OrderBookHeader {
+        base_mint: read_key(PHOENIX_BASE_MINT_OFFSET),
+        quote_mint: read_key(PHOENIX_QUOTE_MINT_OFFSET),
+        base_lot_size: read_u64(PHOENIX_BASE_LOT_SIZE_OFFSET),
+        quote_lot_size: read_u64(PHOENIX_QUOTE_LOT_SIZE_OFFSET),
+    };
+    require!(
+        header.base_lot_size > 0 && header.quote_lot_size > 0,
+        ArbitrageError::InvalidPoolAccount
+    );
+    Ok(header)
+}
+
+// 📘 OPENBOOK V2 INTEGRATION
+//
+// Like Phoenix, a leg is an immediate-or-cancel take order. DEX accounts, in order:
+//   0 openbook program   3 bids   6 quote vault    8 token program
+//   1 market             4 asks   7 event heap     9 system program
+//   2 market authority   5 base vault
+// min_output, scaled to the whole lots spent, becomes the order's limit
+// price, in quote lots per base lot.
+// The user pays any penalty; the optional oracles and open-orders admin are
+// passed as the program id, Anchor's encoding for "none".
+const OPENBOOK_ACCOUNT_COUNT: usize = 10;
+const OPENBOOK_PLACE_TAKE_ORDER_DISCRIMINATOR: [u8; 8] = [3, 44, 71, 3, 26, 199, 203, 85];
+const OPENBOOK_QUOTE_LOT_SIZE_OFFSET: usize = 448;
+const OPENBOOK_BASE_LOT_SIZE_OFFSET: usize = 456;
+const OPENBOOK_BASE_MINT_OFFSET: usize = 576;
+const OPENBOOK_QUOTE_MINT_OFFSET: usize = 608;
+const OPENBOOK_IMMEDIATE_OR_CANCEL: u8 = 1;
+const OPENBOOK_MATCH_LIMIT: u8 = 16;
+
+struct OpenBookOrder {
+    is_ask: bool,
+    price_lots: i64,
+    max_base_lots: i64,
+    max_quote_lots_including_fees: i64,
+}
+
+fn execute_openbook_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing OpenBook v2 swap");
+
+    let [program, market, market_authority, bids, asks, base_vault, quote_vault, event_heap, token_program, system_program] =
+        accounts.dex
+    else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    let header = read_openbook_market_header(market)?;
+    let is_ask = if step.input_mint == header.base_mint && step.output_mint == header.quote_mint {
+        true
+    } else if step.input_mint == header.quote_mint && step.output_mint == header.base_mint {
+        false
+    } else {
+        return err!(ArbitrageError::PoolMintMismatch);
+    };
+    let (base_account, quote_account) = if is_ask {
+        (accounts.input, accounts.output)
+    } else {
+        (accounts.output, accounts.input)
+    };
+
+    // Selling base: at least min_output quote for the base sold, so a price floor.
+    // Buying base: no more quote per base than gets min_output out, so a price cap.
+    let aligned = lot_aligned(
+        input_amount,
+        if is_ask { header.base_lot_size } else { header.quote_lot_size },
+        min_output,
+    )?;
+    let order = if is_ask {
+        let min_quote_lots = aligned.min_output.div_ceil(header.quote_lot_size);
+        OpenBookOrder {
+            is_ask,
+            price_lots: min_quote_lots.div_ceil(aligned.lots).max(1) as i64,
+            max_base_lots: aligned.lots as i64,
+            max_quote_lots_including_fees: i64::MAX,
+        }
+    } else {
+        let min_base_lots = aligned.min_output.div_ceil(header.base_lot_size).max(1);
+        OpenBookOrder {
+            is_ask,
+            price_lots: (aligned.lots / min_base_lots).max(1) as i64,
+            max_base_lots: i64::MAX,
+            max_quote_lots_including_fees: aligned.lots as i64,
+        }
+    };
+
+    let trader = trading_authority(ctx);
+    let openbook_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(trader.key(), true),
+            AccountMeta::new(ctx.accounts.user.key(), true), // penalty payer
+            AccountMeta::new(market.key(), false),
+            AccountMeta::new_readonly(market_authority.key(), false),
+            AccountMeta::new(bids.key(), false),
+            AccountMeta::new(asks.key(), false),
+            AccountMeta::new(base_vault.key(), false),
+            AccountMeta::new(quote_vault.key(), false),
+            AccountMeta::new(event_heap.key(), false),
+            AccountMeta::new(base_account.key(), false),
+            AccountMeta::new(quote_account.key(), false),
+            AccountMeta::new_readonly(program.key(), false), // oracle a: none
+            AccountMeta::new_readonly(program.key(), false), // oracle b: none
+            AccountMeta::new_readonly(token_program.key(), false),
+            AccountMeta::new_readonly(system_program.key(), false),
+            AccountMeta::new_readonly(program.key(), false), // open orders admin: none
+        ],
+        data: create_openbook_swap_data(&order),
+    };
+
+    if dry_run {
+        return plan_swap(&openbook_instruction, step, input_amount);
+    }
+
+    let result = measured_swap(
+        ctx,
+        step,
+        accounts,
+        &openbook_instruction,
+        &[
+            trader,
+            ctx.accounts.user.to_account_info(),
+            market.clone(),
+            market_authority.clone(),
+            bids.clone(),
+            asks.clone(),
+            base_vault.clone(),
+            quote_vault.clone(),
+            event_heap.clone(),
+            base_account.clone(),
+            quote_account.clone(),
+            token_program.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        aligned.input_amount,
+        aligned.min_output,
+    )?;
+
+    // An empty book fills nothing; the leg then fails instead of passing silently
+    Ok(SwapResult { success: result.success && result.output_amount > 0, ..result })
+}
+
+fn read_openbook_market_header(market: &AccountInfo) -> Result<OrderBookHeader> {
+    let data = market.try_borrow_data()?;
+    require!(
+        data.len() >= OPENBOOK_QUOTE_MINT_OFFSET + 32,
+        ArbitrageError::InvalidPoolAccount
+    );
+    let read_key = |offset: usize| {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&data[offset..offset + 32]);
+        Pubkey::new_from_array(key)
+    };
+    // Lot sizes are stored as i64; a non-positive one marks a malformed market
+    let read_lot_size = |offset: usize| {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(&data[offset..offset + 8]);
+        u64::try_from(i64::from_le_bytes(value)).unwrap_or(0)
+    };
+
+    let header = OrderBookHeader {
+        base_mint: read_key(OPENBOOK_BASE_MINT_OFFSET),
+        quote_mint: read_key(OPENBOOK_QUOTE_MINT_OFFSET),
+        base_lot_size: read_lot_size(OPENBOOK_BASE_LOT_SIZE_OFFSET),
+        quote_lot_size: read_lot_size(OPENBOOK_QUOTE_LOT_SIZE_OFFSET),
+    };
+    require!(
+        header.base_lot_size > 0 && header.quote_lot_size > 0,
+        ArbitrageError::InvalidPoolAccount
+    );
+    Ok(header)
+}
+
+// 🧪 LIFINITY INTEGRATION
+//
+// DEX accounts for a Lifinity v2 swap, in order:
+//   0 lifinity program   4 pool destination vault   8 oracle main
+//   1 amm authority      5 pool mint                9 oracle sub
+//   2 amm                6 fee account             10 oracle pc
+//   3 pool source vault  7 token program
+// The vaults are given in swap direction: source holds input_mint.
+const LIFINITY_ACCOUNT_COUNT: usize = 11;
+
+fn execute_lifinity_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Lifinity swap");
+
+    let [program, authority, amm, pool_source, pool_destination, pool_mint, fee_account, token_program, oracle_main, oracle_sub, oracle_pc] =
+        accounts.dex
+    else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    // Vaults passed the wrong way round would swap in the opposite direction
+    require_keys_eq!(load_token_account(pool_source)?.mint, step.input_mint, ArbitrageError::PoolMintMismatch);
+    require_keys_eq!(
+        load_token_account(pool_destination)?.mint,
+        step.output_mint,
+        ArbitrageError::PoolMintMismatch
+    );
+
+    let trader = trading_authority(ctx);
+    let lifinity_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(authority.key(), false),
+            AccountMeta::new(amm.key(), false),
+            AccountMeta::new_readonly(trader.key(), true),
+            AccountMeta::new(accounts.input.key(), false),
+            AccountMeta::new(accounts.output.key(), false),
+            AccountMeta::new(pool_source.key(), false),
+            AccountMeta::new(pool_destination.key(), false),
+            AccountMeta::new(pool_mint.key(), false),
+            AccountMeta::new(fee_account.key(), false),
+            AccountMeta::new_readonly(token_program.key(), false),
+            AccountMeta::new_readonly(oracle_main.key(), false),
+            AccountMeta::new_readonly(oracle_sub.key(), false),
+            AccountMeta::new_readonly(oracle_pc.key(), false),
+        ],
+        data: create_lifinity_swap_data(input_amount, min_output),
+    };
+
+    if dry_run {
+        return plan_swap(&lifinity_instruction, step, input_amount);
+    }
+
+    measured_swap(
+        ctx,
+        step,
+        accounts,
+        &lifinity_instruction,
+        &[
+            authority.clone(),
+            amm.clone(),
+            trader,
+            accounts.input.clone(),
+            accounts.output.clone(),
+            pool_source.clone(),
+            pool_destination.clone(),
+            pool_mint.clone(),
+            fee_account.clone(),
+            token_program.clone(),
+            oracle_main.clone(),
+            oracle_sub.clone(),
+            oracle_pc.clone(),
+            program.clone(),
+        ],
+        input_amount,
+        min_output,
+    )
+}
+
+// 🪙 SABER STABLE-SWAP INTEGRATION
+//
+// DEX accounts for a stable-swap swap, in order:
+//   0 saber program       3 pool source vault        6 token program
+//   1 swap                4 pool destination vault
+//   2 swap authority      5 admin fee destination (output side)
+// The vaults are given in swap direction: source holds input_mint.
+const SABER_ACCOUNT_COUNT: usize = 7;
+const SABER_SWAP_TAG: u8 = 1;
+const SABER_INITIAL_AMP_OFFSET: usize = 3;
+const SABER_TARGET_AMP_OFFSET: usize = 11;
+const SABER_START_RAMP_TS_OFFSET: usize = 19;
+const SABER_STOP_RAMP_TS_OFFSET: usize = 27;
+
+fn execute_saber_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Saber swap");
+
+    let [program, swap, swap_authority, pool_source, pool_destination, admin_fee_destination, token_program] =
+        accounts.dex
+    else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    // Vaults passed the wrong way round would swap in the opposite direction
+    require_keys_eq!(load_token_account(pool_source)?.mint, step.input_mint, ArbitrageError::PoolMintMismatch);
+    require_keys_eq!(
+        load_token_account(pool_destination)?.mint,
+        step.output_mint,
+        ArbitrageError::PoolMintMismatch
+    );
+
+    let trader = trading_authority(ctx);
+    let saber_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(swap.key(), false),
+            AccountMeta::new_readonly(swap_authority.key(), false),
+            AccountMeta::new_readonly(trader.key(), true),
+            AccountMeta::new(accounts.input.key(), false),
+            AccountMeta::new(pool_source.key(), false),
+            AccountMeta::new(pool_destination.key(), false),
+            AccountMeta::new(accounts.output.key(), false),
+            AccountMeta::new(admin_fee_destination.key(), false),
+            AccountMeta::new_readonly(token_program.key(), false),
+        ],
+        data: create_saber_swap_data(input_amount, min_output),
+    };
+
+    if dry_run {
+        return plan_swap(&saber_instruction, step, input_amount);
+    }
+
+    measured_swap(
+        ctx,
+        step,
+        accounts,
+        &saber_instruction,
+        &[
+            swap.clone(),
+            swap_authority.clone(),
+            trader,
+            accounts.input.clone(),
+            pool_source.clone(),
+            pool_destination.clone(),
+            accounts.output.clone(),
+            admin_fee_destination.clone(),
+            token_program.clone(),
+            program.clone(),
+        ],
+        input_amount,
+        min_output,
+    )
+}
+
+// What the pool's invariant pays for input_amount at its current reserves and amplification
+fn saber_quote_output(dex_accounts: &[AccountInfo], input_amount: u64) -> Result<u64> {
+    let [_, swap, _, pool_source, pool_destination, ..] = dex_accounts else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    let reserve_in = load_token_account(pool_source)?.amount;
+    let reserve_out = load_token_account(pool_destination)?.amount;
+    Ok(stable_swap_output(read_saber_amp(swap)?, reserve_in, reserve_out, input_amount)?)
+}
+
+// The amplification coefficient ramps linearly from initial to target between the ramp timestamps
+fn read_saber_amp(swap: &AccountInfo) -> Result<u64> {
+    let data = swap.try_borrow_data()?;
+    require!(data.len() >= SABER_STOP_RAMP_TS_OFFSET + 8, ArbitrageError::InvalidPoolAccount);
+    let read_u64 = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let initial_amp = read_u64(SABER_INITIAL_AMP_OFFSET);
+    let target_amp = read_u64(SABER_TARGET_AMP_OFFSET);
+    let start_ramp = read_u64(SABER_START_RAMP_TS_OFFSET) as i64;
+    let stop_ramp = read_u64(SABER_STOP_RAMP_TS_OFFSET) as i64;
+
+    let now = Clock::get()?.unix_timestamp;
+    if now >= stop_ramp || stop_ramp <= start_ramp {
+        return Ok(target_amp);
+    }
+    let elapsed = now.saturating_sub(start_ramp).max(0) as u64;
+    let duration = (stop_ramp - start_ramp) as u64;
+    Ok(if target_amp >= initial_amp {
+        initial_amp + checked_mul_div(target_amp - initial_amp, elapsed, duration)?
+    } else {
+        initial_amp - checked_mul_div(initial_amp - target_amp, elapsed, duration)?
+    })
+}
+
+// 💠 CREMA CLMM INTEGRATION
+//
+// DEX accounts for a Crema concentrated-liquidity swap, in order:
+//   0 crema program    4 token b mint      8 partner            12.. tick arrays
+//   1 clmm config      5 token a vault     9 partner token a
+//   2 clmmpool         6 token b vault    10 partner token b
+//   3 token a mint     7 tick array map   11 token program
+// Like Orca, the user's accounts are slotted into the a/b positions by the
+// swap direction, read from the pool's mints. route_data[0] holds the number
+// of tick arrays, which are passed in the order the swap crosses them.
+const CREMA_ACCOUNT_COUNT: usize = 12;
+const CLMMPOOL_TOKEN_A_OFFSET: usize = 40;
+const CLMMPOOL_TOKEN_B_OFFSET: usize = 72;
+
+fn execute_crema_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Crema swap");
+
+    require!(accounts.dex.len() > CREMA_ACCOUNT_COUNT, ArbitrageError::MissingDexAccounts);
+    let (fixed, tick_arrays) = accounts.dex.split_at(CREMA_ACCOUNT_COUNT);
+    let [program, clmm_config, clmmpool, token_a_mint, token_b_mint, vault_a, vault_b, tick_array_map, partner, partner_token_a, partner_token_b, token_program] =
+        fixed
+    else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    // a_to_b when we're selling the pool's token A
+    let (mint_a, mint_b) = read_clmmpool_mints(clmmpool)?;
+    require_keys_eq!(token_a_mint.key(), mint_a, ArbitrageError::PoolMintMismatch);
+    require_keys_eq!(token_b_mint.key(), mint_b, ArbitrageError::PoolMintMismatch);
+    let a_to_b = if step.input_mint == mint_a && step.output_mint == mint_b {
+        true
+    } else if step.input_mint == mint_b && step.output_mint == mint_a {
+        false
+    } else {
+        return err!(ArbitrageError::PoolMintMismatch);
+    };
+    let (owner_a, owner_b) = if a_to_b {
+        (accounts.input, accounts.output)
+    } else {
+        (accounts.output, accounts.input)
+    };
+
+    let trader = trading_authority(ctx);
+    let mut metas = vec![
+        AccountMeta::new_readonly(clmm_config.key(), false),
+        AccountMeta::new(clmmpool.key(), false),
+        AccountMeta::new_readonly(token_a_mint.key(), false),
+        AccountMeta::new_readonly(token_b_mint.key(), false),
+        AccountMeta::new(owner_a.key(), false),
+        AccountMeta::new(owner_b.key(), false),
+        AccountMeta::new(vault_a.key(), false),
+        AccountMeta::new(vault_b.key(), false),
+        AccountMeta::new(tick_array_map.key(), false),
+        AccountMeta::new_readonly(trader.key(), true),
+        AccountMeta::new_readonly(partner.key(), false),
+        AccountMeta::new(partner_token_a.key(), false),
+        AccountMeta::new(partner_token_b.key(), false),
+        AccountMeta::new_readonly(token_program.key(), false),
+    ];
+    metas.extend(tick_arrays.iter().map(|info| AccountMeta::new(info.key(), false)));
+
+    let mut infos = vec![
+        clmm_config.clone(),
+        clmmpool.clone(),
+        token_a_mint.clone(),
+        token_b_mint.clone(),
+        owner_a.clone(),
+        owner_b.clone(),
+        vault_a.clone(),
+        vault_b.clone(),
+        tick_array_map.clone(),
+        trader,
+        partner.clone(),
+        partner_token_a.clone(),
+        partner_token_b.clone(),
+        token_program.clone(),
+        program.clone(),
+    ];
+    infos.extend(tick_arrays.iter().cloned());
+
+    let crema_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: metas,
+        data: create_crema_swap_data(input_amount, min_output, a_to_b),
+    };
+
+    if dry_run {
+        return plan_swap(&crema_instruction, step, input_amount);
+    }
+
+    measured_swap(ctx, step, accounts, &crema_instruction, &infos, input_amount, min_output)
+}
+
+fn crema_tick_array_count(step: &SwapStep) -> Result<usize> {
+    let count = *step.route_data.first().ok_or(ArbitrageError::InvalidRouteData)?;
+    require!(count > 0, ArbitrageError::InvalidRouteData);
+    Ok(count as usize)
+}
+
+fn read_clmmpool_mints(clmmpool: &AccountInfo) -> Result<(Pubkey, Pubkey)> {
+    let data = clmmpool.try_borrow_data()?;
+    require!(data.len() >= CLMMPOOL_TOKEN_B_OFFSET + 32, ArbitrageError::InvalidPoolAccount);
+    let read_key = |offset: usize| {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&data[offset..offset + 32]);
+        Pubkey::new_from_array(key)
+    };
+    Ok((read_key(CLMMPOOL_TOKEN_A_OFFSET), read_key(CLMMPOOL_TOKEN_B_OFFSET)))
+}
+
+// ♾️ INVARIANT INTEGRATION
+//
+// DEX accounts for an Invariant CLMM swap, in order:
+//   0 invariant program   3 tickmap     6 program authority   8.. ticks
+//   1 state               4 reserve x   7 token program
+//   2 pool                5 reserve y
+// route_data[0] holds the number of initialized ticks the swap may cross
+// (possibly zero), passed in crossing order. Invariant's swap takes no
+// output floor, so min_output is enforced here from the measured delta.
+const INVARIANT_ACCOUNT_COUNT: usize = 8;
+const INVARIANT_POOL_TOKEN_X_OFFSET: usize = 8;
+const INVARIANT_POOL_TOKEN_Y_OFFSET: usize = 40;
+const INVARIANT_MIN_SQRT_PRICE: u128 = 15258932000000000000;
+const INVARIANT_MAX_SQRT_PRICE: u128 = 65535383934512647000000000000;
+
+fn execute_invariant_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing Invariant swap");
+
+    require!(accounts.dex.len() >= INVARIANT_ACCOUNT_COUNT, ArbitrageError::MissingDexAccounts);
+    let (fixed, ticks) = accounts.dex.split_at(INVARIANT_ACCOUNT_COUNT);
+    let [program, state, pool, tickmap, reserve_x, reserve_y, program_authority, token_program] = fixed else {
+        return err!(ArbitrageError::MissingDexAccounts);
+    };
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    // x_to_y when we're selling the pool's token X
+    let (mint_x, mint_y) = read_invariant_pool_mints(pool)?;
+    let x_to_y = if step.input_mint == mint_x && step.output_mint == mint_y {
+        true
+    } else if step.input_mint == mint_y && step.output_mint == mint_x {
+        false
+    } else {
+        return err!(ArbitrageError::PoolMintMismatch);
+    };
+    let (account_x, account_y) = if x_to_y {
+        (accounts.input, accounts.output)
+    } else {
+        (accounts.output, accounts.input)
+    };
+
+    let trader = trading_authority(ctx);
+    let mut metas = vec![
+        AccountMeta::new_readonly(state.key(), false),
+        AccountMeta::new(pool.key(), false),
+        AccountMeta::new(tickmap.key(), false),
+        AccountMeta::new(account_x.key(), false),
+        AccountMeta::new(account_y.key(), false),
+        AccountMeta::new(reserve_x.key(), false),
+        AccountMeta::new(reserve_y.key(), false),
+        AccountMeta::new_readonly(trader.key(), true),
+        AccountMeta::new_readonly(program_authority.key(), false),
+        AccountMeta::new_readonly(token_program.key(), false),
+    ];
+    metas.extend(ticks.iter().map(|info| AccountMeta::new(info.key(), false)));
+
+    let mut infos = vec![
+        state.clone(),
+        pool.clone(),
+        tickmap.clone(),
+        account_x.clone(),
+        account_y.clone(),
+        reserve_x.clone(),
+        reserve_y.clone(),
+        trader,
+        program_authority.clone(),
+        token_program.clone(),
+        program.clone(),
+    ];
+    infos.extend(ticks.iter().cloned());
+
+    let invariant_instruction = Instruction {
+        program_id: step.program_id,
+        accounts: metas,
+        data: create_invariant_swap_data(input_amount, x_to_y),
+    };
+
+    if dry_run {
+        return plan_swap(&invariant_instruction, step, input_amount);
+    }
+
+    let result = measured_swap(ctx, step, accounts, &invariant_instruction, &infos, input_amount, min_output)?;
+
+    // The venue fills to the price limit, so the floor is ours alone to hold
+    require!(result.success, ArbitrageError::SlippageExceeded);
+    Ok(result)
+}
+
+fn invariant_tick_count(step: &SwapStep) -> Result<usize> {
+    Ok(*step.route_data.first().ok_or(ArbitrageError::InvalidRouteData)? as usize)
+}
+
+fn read_invariant_pool_mints(pool: &AccountInfo) -> Result<(Pubkey, Pubkey)> {
+    let data = pool.try_borrow_data()?;
+    require!(data.len() >= INVARIANT_POOL_TOKEN_Y_OFFSET + 32, ArbitrageError::InvalidPoolAccount);
+    let read_key = |offset: usize| {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&data[offset..offset + 32]);
+        Pubkey::new_from_array(key)
+    };
+    Ok((read_key(INVARIANT_POOL_TOKEN_X_OFFSET), read_key(INVARIANT_POOL_TOKEN_Y_OFFSET)))
+}
+
+// 🧩 CUSTOM PROGRAMS
+//
+// For venues without a dedicated integration. route_data[0] is the number of
+// accounts the program takes and the rest of route_data is its instruction
+// data, passed through untouched. DEX accounts are the program followed by
+// those accounts. The first is the program's transfer authority: the input
+// account is approved to it for input_amount and revoked after the call.
+// The user's input and output accounts go first, then that delegate, then
+// the rest with their writable flags copied from this transaction.
+//
+// The program is chosen by the route, so it never gets the trading
+// authority's signature (nor any other: every signer flag is cleared).
+// What it can take is capped at the approved input_amount.
+//
+// The program is never told min_output; it's enforced on the balance change
+// afterwards, like every other leg.
+fn custom_account_count(step: &SwapStep) -> Result<usize> {
+    let count = *step.route_data.first().ok_or(ArbitrageError::InvalidRouteData)?;
+    Ok(count as usize)
+}
+
+fn execute_custom_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    input_amount: u64,
+    min_output: u64,
+    dry_run: bool,
+) -> Result<SwapResult> {
+    trace_msg!(ctx.accounts.config, "Executing custom swap via {}", step.program_id);
+
+    let (program, extra) = accounts.dex.split_first().ok_or(ArbitrageError::MissingDexAccounts)?;
+    require_keys_eq!(program.key(), step.program_id, ArbitrageError::DexProgramMismatch);
+
+    let (delegate, extra) = extra.split_first().ok_or(ArbitrageError::MissingDexAccounts)?;
+    let custom_instruction = create_custom_swap_instruction(step, accounts, delegate, extra);
+
+    if dry_run {
+        return plan_swap(&custom_instruction, step, input_amount);
+    }
+
+    // The input account's own token program handles the approval
+    let input_token_program = *accounts.input.owner;
+    require!(is_token_program(&input_token_program), ArbitrageError::InvalidTokenAccount);
+    let token_program = if input_token_program == ctx.accounts.token_program.key() {
+        ctx.accounts.token_program.to_account_info()
+    } else {
+        find_remaining_account(ctx, input_token_program).ok_or(ArbitrageError::MissingDexAccounts)?.clone()
+    };
+
+    with_trader_seeds(ctx, |signer_seeds| {
+        token_interface::approve(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                Approve {
+                    to: accounts.input.clone(),
+                    delegate: delegate.clone(),
+                    authority: trading_authority(ctx),
+                },
+                signer_seeds,
+            ),
+            input_amount,
+        )
+    })?;
+
+    let mut infos = vec![accounts.input.clone(), accounts.output.clone(), delegate.clone()];
+    infos.extend(extra.iter().cloned());
+    infos.push(program.clone());
+    let result = measured_swap(ctx, step, accounts, &custom_instruction, &infos, input_amount, min_output)?;
+
+    // Whatever the program left of its allowance goes with the approval
+    with_trader_seeds(ctx, |signer_seeds| {
+        token_interface::revoke(CpiContext::new_with_signer(
+            token_program,
+            Revoke { source: accounts.input.clone(), authority: trading_authority(ctx) },
+            signer_seeds,
+        ))
+    })?;
+    Ok(result)
+}
+
+// Nothing in a custom leg's accounts is passed as a signer, so the program
+// can only move what's approved to its delegate
+fn create_custom_swap_instruction(
+    step: &SwapStep,
+    accounts: &StepAccounts,
+    delegate: &AccountInfo,
+    extra: &[AccountInfo],
+) -> Instruction {
+    let mut metas = vec![
+        AccountMeta::new(accounts.input.key(), false),
+        AccountMeta::new(accounts.output.key(), false),
+        AccountMeta::new_readonly(delegate.key(), false),
+    ];
+    metas.extend(extra.iter().map(|info| AccountMeta {
+        pubkey: info.key(),
+        is_signer: false,
+        is_writable: info.is_writable,
+    }));
+    Instruction {
+        program_id: step.program_id,
+        accounts: metas,
+        data: step.route_data[1..].to_vec(),
+    }
+}
+
+// 📊 HELPER FUNCTIONS
+// Whirlpool, Meteora, Lifinity, Crema and Invariant are Anchor programs whose
+// instruction is named `swap`, so they share its sighash("global:swap")
+const ANCHOR_SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+
+fn create_jupiter_swap_data(input_amount: u64, min_output: u64, route_data: Vec<u8>) -> Vec<u8> {
+    // Create Jupiter-compatible instruction data
+    let mut data = Vec::new();
+    data.extend_from_slice(&input_amount.to_le_bytes());
+    data.extend_from_slice(&min_output.to_le_bytes());
+    data.extend_from_slice(&route_data);
+    data
+}
+
+fn create_raydium_swap_data(input_amount: u64, min_output: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(17);
+    data.push(RAYDIUM_SWAP_BASE_IN);
+    data.extend_from_slice(&input_amount.to_le_bytes());
+    data.extend_from_slice(&min_output.to_le_bytes());
+    data
+}
+
+// Exact-output swap: spend at most max_amount_in to receive exactly amount_out
+fn create_raydium_swap_base_out_data(max_amount_in: u64, amount_out: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(17);
+    data.push(RAYDIUM_SWAP_BASE_OUT);
+    data.extend_from_slice(&max_amount_in.to_le_bytes());
+    data.extend_from_slice(&amount_out.to_le_bytes());
+    data
+}
+
+// amount is the input when amount_specified_is_input, otherwise the exact output;
+// other_amount_threshold is then the minimum output or the maximum input respectively
+fn create_whirlpool_swap_data(
+    amount: u64,
+    other_amount_threshold: u64,
+    amount_specified_is_input: bool,
+    a_to_b: bool,
+) -> Vec<u8> {
+    let sqrt_price_limit = if a_to_b {
+        WHIRLPOOL_MIN_SQRT_PRICE
+    } else {
+        WHIRLPOOL_MAX_SQRT_PRICE
+    };
+
+    let mut data = Vec::with_capacity(42);
+    data.extend_from_slice(&ANCHOR_SWAP_DISCRIMINATOR);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&other_amount_threshold.to_le_bytes());
+    data.extend_from_slice(&sqrt_price_limit.to_le_bytes());
+    data.push(amount_specified_is_input as u8);
+    data.push(a_to_b as u8);
+    data
+}
+
+fn create_meteora_swap_data(input_amount: u64, min_output: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(24);
+    data.extend_from_slice(&ANCHOR_SWAP_DISCRIMINATOR);
+    data.extend_from_slice(&input_amount.to_le_bytes());
+    data.extend_from_slice(&min_output.to_le_bytes());
+    data
+}
+
+struct PhoenixOrder {
+    is_ask: bool,
+    num_base_lots: u64,
+    num_quote_lots: u64,
+    min_base_lots_to_fill: u64,
+    min_quote_lots_to_fill: u64,
+}
+
+fn create_phoenix_swap_data(order: &PhoenixOrder) -> Vec<u8> {
+    // Swap { OrderPacket::ImmediateOrCancel { .. } }, borsh-encoded
+    let mut data = Vec::with_capacity(64);
+    data.push(PHOENIX_SWAP_TAG);
+    data.push(2); // OrderPacket::ImmediateOrCancel
+    data.push(order.is_ask as u8); // side: Bid = 0, Ask = 1
+    data.push(0); // price_in_ticks: None (take any price, bounded by min fill)
+    data.extend_from_slice(&order.num_base_lots.to_le_bytes());
+    data.extend_from_slice(&order.num_quote_lots.to_le_bytes());
+    data.extend_from_slice(&order.min_base_lots_to_fill.to_le_bytes());
+    data.extend_from_slice(&order.min_quote_lots_to_fill.to_le_bytes());
+    data.push(0); // self_trade_behavior: Abort
+    data.push(0); // match_limit: None
+    data.extend_from_slice(&0u128.to_le_bytes()); // client_order_id
+    data.push(0); // use_only_deposited_funds
+    data.push(0); // last_valid_slot: None
+    data.push(0); // last_valid_unix_timestamp_in_seconds: None
+    data
+}
+
+fn create_lifinity_swap_data(input_amount: u64, min_output: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(24);
+    data.extend_from_slice(&ANCHOR_SWAP_DISCRIMINATOR);
+    data.extend_from_slice(&input_amount.to_le_bytes());
+    data.extend_from_slice(&min_output.to_le_bytes());
+    data
+}
+
+fn create_saber_swap_data(input_amount: u64, min_output: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(17);
+    data.push(SABER_SWAP_TAG);
+    data.extend_from_slice(&input_amount.to_le_bytes());
+    data.extend_from_slice(&min_output.to_le_bytes());
+    data
+}
+
+fn create_crema_swap_data(input_amount: u64, min_output: u64, a_to_b: bool) -> Vec<u8> {
+    // Crema prices are Q64.64 sqrt prices with the same bounds as Whirlpool's
+    let sqrt_price_limit = if a_to_b {
+        WHIRLPOOL_MIN_SQRT_PRICE
+    } else {
+        WHIRLPOOL_MAX_SQRT_PRICE
+    };
+
+    // swap(a_to_b, by_amount_in, amount, amount_limit, sqrt_price_limit)
+    let mut data = Vec::with_capacity(42);
+    data.extend_from_slice(&ANCHOR_SWAP_DISCRIMINATOR);
+    data.push(a_to_b as u8);
+    data.push(true as u8);
+    data.extend_from_slice(&input_amount.to_le_bytes());
+    data.extend_from_slice(&min_output.to_le_bytes());
+    data.extend_from_slice(&sqrt_price_limit.to_le_bytes());
+    data
+}
+
+fn create_invariant_swap_data(input_amount: u64, x_to_y: bool) -> Vec<u8> {
+    let sqrt_price_limit = if x_to_y {
+        INVARIANT_MIN_SQRT_PRICE
+    } else {
+        INVARIANT_MAX_SQRT_PRICE
+    };
+
+    // swap(x_to_y, amount, by_amount_in, sqrt_price_limit)
+    let mut data = Vec::with_capacity(34);
+    data.extend_from_slice(&ANCHOR_SWAP_DISCRIMINATOR);
+    data.push(x_to_y as u8);
+    data.extend_from_slice(&input_amount.to_le_bytes());
+    data.push(true as u8);
+    data.extend_from_slice(&sqrt_price_limit.to_le_bytes());
+    data
+}
+
+fn create_openbook_swap_data(order: &OpenBookOrder) -> Vec<u8> {
+    // place_take_order(PlaceTakeOrderArgs), borsh-encoded
+    let mut data = Vec::with_capacity(35);
+    data.extend_from_slice(&OPENBOOK_PLACE_TAKE_ORDER_DISCRIMINATOR);
+    data.push(order.is_ask as u8); // side: Bid = 0, Ask = 1
+    data.extend_from_slice(&order.price_lots.to_le_bytes());
+    data.extend_from_slice(&order.max_base_lots.to_le_bytes());
+    data.extend_from_slice(&order.max_quote_lots_including_fees.to_le_bytes());
+    data.push(OPENBOOK_IMMEDIATE_OR_CANCEL);
+    data.push(OPENBOOK_MATCH_LIMIT);
+    data
+}
+
+fn project_step_output(input_amount: u64, expected_rate: u64) -> Result<u64> {
+    Ok(checked_mul_div(input_amount, expected_rate, 1000)?)
+}
+
+// Runs a leg's swap CPI and measures what it delivered from the output
+// account's balance before and after. Only the balance change counts as
+// output; the account may already hold tokens, and the DEX's own return
+// value is never trusted. A DEX that errors aborts the whole transaction
+// before control returns, and the runtime's log names the failing program.
+fn measured_swap<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, ExecuteArbitrageRoute<'info>>,
+    step: &SwapStep,
+    accounts: &StepAccounts<'_, 'info>,
+    instruction: &Instruction,
+    infos: &[AccountInfo],
+    input_amount: u64,
+    min_output: u64,
+) -> Result<SwapResult> {
+    let balance_before = load_token_balance(accounts.output)?;
+    with_trader_seeds(ctx, |signer_seeds| invoke_signed(instruction, infos, signer_seeds))?;
+    let received = load_token_balance(accounts.output)?.received_since(&balance_before);
+    swap_result(step, input_amount, min_output, &received)
+}
+
+// A leg fills only if the DEX delivered at least min_output, transfer fee included
+fn swap_result(step: &SwapStep, input_amount: u64, min_output: u64, received: &ReceivedAmount) -> Result<SwapResult> {
+    Ok(SwapResult {
+        success: received.delivered() >= min_output,
+        output_amount: received.amount,
+        slippage: calculate_slippage(project_step_output(input_amount, step.expected_rate)?, received.delivered()),
+        transfer_fee: received.transfer_fee,
+    })
+}
+
+// Dry run: log the CPI a leg would make and assume it fills at expected_rate
+fn plan_swap(instruction: &Instruction, step: &SwapStep, input_amount: u64) -> Result<SwapResult> {
+    msg!("[dry run] CPI to {} ({} bytes of data)", instruction.program_id, instruction.data.len());
+    for (index, meta) in instruction.accounts.iter().enumerate() {
+        msg!(
+            "[dry run]   #{} {} writable={} signer={}",
+            index, meta.pubkey, meta.is_writable, meta.is_signer
+        );
+    }
+
+    let projected_output = project_step_output(input_amount, step.expected_rate)?;
+    Ok(SwapResult {
+        success: true,
+        output_amount: projected_output,
+        slippage: calculate_slippage(projected_output, projected_output),
+        transfer_fee: 0,
+    })
+}
+
+// What a recorded pool state pays for input_amount
+fn snapshot_output(snapshot: &PoolStateSnapshot, input_amount: u64) -> Result<u64> {
+    require!(snapshot.fee_bps <= 10000, ArbitrageError::InvalidFee);
+    require!(
+        snapshot.reserve_in > 0 && snapshot.reserve_out > 0,
+        ArbitrageError::InsufficientLiquidity
+    );
+    let input_after_fee = checked_mul_div(input_amount, 10000 - snapshot.fee_bps as u64, 10000)?;
+    Ok(match snapshot.amp {
+        Some(amp) => stable_swap_output(amp, snapshot.reserve_in, snapshot.reserve_out, input_after_fee)?,
+        None => constant_product_output(snapshot.reserve_in, snapshot.reserve_out, input_after_fee)?,
+    })
+}
+
+// Each leg's output when input_amount is fed through the recorded pools in turn
+fn replay_leg_outputs(snapshots: &[PoolStateSnapshot], input_amount: u64) -> Result<Vec<u64>> {
+    let mut current_amount = input_amount;
+    let mut leg_outputs = Vec::with_capacity(snapshots.len());
+    for (step_index, snapshot) in snapshots.iter().enumerate() {
+        current_amount = snapshot_output(snapshot, current_amount)?;
+        msg!("Step {} replayed output: {}", step_index + 1, current_amount);
+        leg_outputs.push(current_amount);
+    }
+    Ok(leg_outputs)
+}
+
+// What the route's expected_rates promise to turn input_amount into
+fn project_route_output(route: &[SwapStep], input_amount: u64, config: &ProgramConfig) -> Result<u64> {
+    let outputs = project_leg_outputs(route, input_amount, config, |_| 0)?;
+    Ok(outputs.last().copied().unwrap_or(input_amount))
+}
+
+// Each leg's output at its expected_rate, after the venue fee fee_bps gives it
+fn project_leg_outputs(
+    route: &[SwapStep],
+    input_amount: u64,
+    config: &ProgramConfig,
+    fee_bps: impl Fn(&SwapStep) -> u16,
+) -> Result<Vec<u64>> {
+    let mut current_amount = input_amount;
+    let mut outputs = Vec::with_capacity(route.len());
+    for (step_index, step) in route.iter().enumerate() {
+        current_amount = after_venue_fee(project_step_output(current_amount, step.expected_rate)?, fee_bps(step))?;
+        trace_msg!(config, "Step {} projected output: {}", step_index + 1, current_amount);
+        outputs.push(current_amount);
+    }
+    Ok(outputs)
+}
+
+// A quote with the venue's swap fee taken off. Rounded up, since min_output is
+// built on it and a floored bound would let a fill slip a unit further.
+fn after_venue_fee(quoted_output: u64, fee_bps: u16) -> Result<u64> {
+    Ok(checked_mul_div_rounded(quoted_output, 10000 - fee_bps as u64, 10000, Rounding::Ceil)?)
+}
+
+// Profit the route's expected_rates promise on input_amount, before fees
+fn project_route_profit_bps(route: &[SwapStep], input_amount: u64, config: &ProgramConfig) -> Result<u64> {
+    let output = project_route_output(route, input_amount, config)?;
+    calculate_profit_bps(output.saturating_sub(input_amount), input_amount)
+}
+
+// Compute units spent since `start` was sampled. Off-chain builds without the
+// syscall read 0 remaining, which makes this 0 rather than garbage.
+// Only the capped portion (or the loan) is put at risk; the rest stays untouched
+fn starting_trade_amount(call_start_balance: u64, max_input_amount: u64, flash_loan_amount: u64) -> Result<u64> {
+    let trade_amount = if flash_loan_amount > 0 {
+        flash_loan_amount
+    } else {
+        require!(call_start_balance > 0, ArbitrageError::ZeroStartBalance);
+        call_start_balance.min(max_input_amount)
+    };
+    require!(trade_amount > 0, ArbitrageError::ZeroStartBalance);
+    Ok(trade_amount)
+}
+
+fn compute_used_since(start: u64) -> u64 {
+    start.saturating_sub(sol_remaining_compute_units())
+}
+
+// Identifies the exact route: ties checkpoints to it and lets fills be reconciled off-chain
+fn hash_route(route: &[SwapStep]) -> Result<[u8; 32]> {
+    let mut data = Vec::new();
+    route.serialize(&mut data)?;
+    Ok(hash(&data).to_bytes())
+}
+
+fn calculate_bps_share(amount: u64, bps: u16) -> Result<u64> {
+    Ok(checked_mul_div(amount, bps as u64, 10000)?)
+}
+
+// Saturates at u64::MAX instead of failing. The math is u128 inside, so only
+// a tiny base against an enormous profit can overflow the result, and a run
+// that profitable should still settle and report rather than revert.
+fn calculate_profit_bps(profit: u64, base_amount: u64) -> Result<u64> {
+    match checked_mul_div(profit, 10000, base_amount) {
+        Err(ArbitrageError::MathOverflow) => Ok(u64::MAX),
+        result => Ok(result?),
+    }
+}
+
+fn calculate_slippage(expected: u64, actual: u64) -> Slippage {
+    let (diff, direction) = match actual.cmp(&expected) {
+        std::cmp::Ordering::Less => (expected - actual, SlippageDirection::Unfavorable),
+        std::cmp::Ordering::Equal => (0, SlippageDirection::Exact),
+        std::cmp::Ordering::Greater => (actual - expected, SlippageDirection::Favorable),
+    };
+    if expected == 0 || diff == 0 {
+        return Slippage { bps: 0, direction };
+    }
+    // Saturate rather than fail: a wildly off fill is still reportable
+    let bps = checked_mul_div(diff, 10000, expected).map_or(u16::MAX, |bps| bps.min(u16::MAX as u64) as u16);
+    Slippage { bps, direction }
+}
+
+// 🏗️ ACCOUNT STRUCTURES
+#[derive(Accounts)]
+pub struct ExecuteArbitrageRoute<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    // The trading authority's ATA: the vault's when one is passed, else the user's
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::token_program = token_program,
+        constraint = user_token_account.key() == get_associated_token_address_with_program_id(
+            &vault.as_ref().map_or(user.key(), |vault| vault.key()),
+            &token_mint.key(),
+            &token_program.key(),
+        ) @ ArbitrageError::TokenAccountMismatch,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + ExecutionLock::INIT_SPACE,
+        seeds = [EXECUTION_LOCK_SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub execution_lock: Account<'info, ExecutionLock>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserStats::INIT_SPACE,
+        seeds = [USER_STATS_SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub user_stats: Account<'info, UserStats>,
+
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    // Fees accrue to the config PDA's token account for the traded mint
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [DEX_REGISTRY_SEED], bump = dex_registry.bump)]
+    pub dex_registry: Account<'info, DexRegistry>,
+
+    #[account(seeds = [MINT_ALLOWLIST_SEED], bump = mint_allowlist.bump)]
+    pub mint_allowlist: Account<'info, MintAllowlist>,
+
+    // Receives the net profit when set; otherwise it stays in user_token_account
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::token_program = token_program,
+    )]
+    pub profit_recipient: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // Required when config.strategist_bps is set; must be owned by config.strategist
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::token_program = token_program,
+    )]
+    pub strategist_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ROUTE_CHECKPOINT_SEED, user.key().as_ref()],
+        bump = route_checkpoint.bump,
+    )]
+    pub route_checkpoint: Option<Account<'info, RouteCheckpoint>>,
+
+    /// CHECK: only receives lamports; required when jito_tip_lamports > 0
+    #[account(mut)]
+    pub jito_tip_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Pyth feed pricing token_mint in USD; owner and layout checked by read_pyth_price
+    pub usd_price_feed: Option<UncheckedAccount<'info>>,
+
+    // Trade from the user's vault instead of their own token accounts
+    #[account(seeds = [VAULT_SEED, user.key().as_ref()], bump = vault.bump)]
+    pub vault: Option<Account<'info, TradingVault>>,
+
+    // Required only when wrap_sol creates a temporary WSOL account
+    #[account(address = NATIVE_MINT)]
+    pub native_mint: Option<InterfaceAccount<'info, Mint>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ProgramConfig::INIT_SPACE,
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::GraphArbitrage>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(payer.key()) @ ArbitrageError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ReportExecution<'info> {
+    pub reporter: Signer<'info>,
+
+    #[account(mut, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RescueTokens<'info> {
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = config,
+        token::token_program = token_program,
+    )]
+    pub source: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program,
+    )]
+    pub recipient: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = config,
+        associated_token::token_program = token_program,
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::token_program = token_program,
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeDexRegistry<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + DexRegistry::INIT_SPACE,
+        seeds = [DEX_REGISTRY_SEED],
+        bump,
+    )]
+    pub dex_registry: Account<'info, DexRegistry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDexRegistry<'info> {
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(mut, seeds = [DEX_REGISTRY_SEED], bump = dex_registry.bump)]
+    pub dex_registry: Account<'info, DexRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct InitVault<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + TradingVault::INIT_SPACE,
+        seeds = [VAULT_SEED, owner.key().as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, TradingVault>,
+
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = token_mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VaultTransfer<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [VAULT_SEED, owner.key().as_ref()], bump = vault.bump, has_one = owner)]
+    pub vault: Account<'info, TradingVault>,
+
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = owner,
+        token::token_program = token_program,
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeMintAllowlist<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + MintAllowlist::INIT_SPACE,
+        seeds = [MINT_ALLOWLIST_SEED],
+        bump,
+    )]
+    pub mint_allowlist: Account<'info, MintAllowlist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMintAllowlist<'info> {
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(mut, seeds = [MINT_ALLOWLIST_SEED], bump = mint_allowlist.bump)]
+    pub mint_allowlist: Account<'info, MintAllowlist>,
+}
+
+#[derive(Accounts)]
+pub struct SimulateArbitrage<'info> {
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteRoute<'info> {
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(seeds = [DEX_REGISTRY_SEED], bump = dex_registry.bump)]
+    pub dex_registry: Account<'info, DexRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct OpenRouteCheckpoint<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + RouteCheckpoint::INIT_SPACE,
+        seeds = [ROUTE_CHECKPOINT_SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub route_checkpoint: Account<'info, RouteCheckpoint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRouteCheckpoint<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [ROUTE_CHECKPOINT_SEED, user.key().as_ref()],
+        bump = route_checkpoint.bump,
+    )]
+    pub route_checkpoint: Account<'info, RouteCheckpoint>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyCancel<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [EXECUTION_LOCK_SEED, user.key().as_ref()],
+        bump = execution_lock.bump,
+    )]
+    pub execution_lock: Option<Account<'info, ExecutionLock>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [ROUTE_CHECKPOINT_SEED, user.key().as_ref()],
+        bump = route_checkpoint.bump,
+    )]
+    pub route_checkpoint: Option<Account<'info, RouteCheckpoint>>,
+
+    // Required only when token accounts are passed for closing
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+// 📋 DATA STRUCTURES
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct VersionedSwapStep {
+    pub version: u8,
+    pub data: Vec<u8>, // Borsh encoding of the step in that version's layout
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SwapStepV1 {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub dex: DexType,
+    pub program_id: Pubkey,
+    pub expected_rate: u64,
+    pub route_data: Vec<u8>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SwapStepV3 {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub dex: DexType,
+    pub program_id: Pubkey,
+    pub expected_rate: u64,
+    pub route_data: Vec<u8>,
+    pub slippage_bps: Option<u16>,
+    pub account_indices: Option<Vec<u8>>,
+    pub split: Option<Vec<SplitLeg>>,
+    pub remainder_dex: Option<DexType>,
+    pub exact_output: Option<u64>,
+    pub oracle: Option<StepOracle>,
+    pub liquidity: Option<PoolLiquidity>,
+    pub live_reserves: Option<LiveReserves>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SwapStepV4 {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub dex: DexType,
+    pub program_id: Pubkey,
+    pub expected_rate: u64,
+    pub route_data: Vec<u8>,
+    pub slippage_bps: Option<u16>,
+    pub account_indices: Option<Vec<u8>>,
+    pub split: Option<Vec<SplitLeg>>,
+    pub remainder_dex: Option<DexType>,
+    pub exact_output: Option<u64>,
+    pub oracle: Option<StepOracle>,
+    pub liquidity: Option<PoolLiquidity>,
+    pub live_reserves: Option<LiveReserves>,
+    pub expected_output: Option<u64>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SwapStepV2 {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub dex: DexType,
+    pub program_id: Pubkey,
+    pub expected_rate: u64,
+    pub route_data: Vec<u8>,
+    pub slippage_bps: Option<u16>,
+    pub account_indices: Option<Vec<u8>>,
+    pub split: Option<Vec<SplitLeg>>,
+    pub remainder_dex: Option<DexType>,
+    pub exact_output: Option<u64>,
+    pub oracle: Option<StepOracle>,
+    pub liquidity: Option<PoolLiquidity>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SwapStep {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub dex: DexType,
+    pub program_id: Pubkey,
+    pub expected_rate: u64, // Rate * 1000 for precision
+    pub route_data: Vec<u8>, // DEX-specific routing data
+    pub slippage_bps: Option<u16>, // Overrides max_slippage_bps for this step
+    pub account_indices: Option<Vec<u8>>, // DEX accounts by remaining_accounts index (lookup tables)
+    pub split: Option<Vec<SplitLeg>>, // Fan the leg out across venues; overrides dex/program_id/route_data
+    pub remainder_dex: Option<DexType>, // Swaps whatever input the primary leaves unspent (partial fills)
+    pub exact_output: Option<u64>, // Final leg only: receive exactly this, spending at most the input
+    pub oracle: Option<StepOracle>, // Bound min_output by an oracle price instead of expected_rate
+    pub liquidity: Option<PoolLiquidity>, // Reserve floor checked before the swap
+    pub live_reserves: Option<LiveReserves>, // Quote from the pool's vaults instead of expected_rate
+    pub expected_output: Option<u64>, // Client's quote for the leg; must agree with expected_rate
+    pub input_account: Option<u8>, // remaining_accounts index to spend from instead of the previous output
+    pub output_account: Option<u8>, // remaining_accounts index to receive into instead of a positional slot
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LiveReserves {
+    pub input_vault: Pubkey, // Pool vault holding input_mint, passed in remaining_accounts
+    pub output_vault: Pubkey, // Pool vault holding output_mint, passed in remaining_accounts
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PoolLiquidity {
+    pub reserves: Vec<Pubkey>, // Pool vault token accounts, passed in remaining_accounts
+    pub min_pool_liquidity: u64, // Every reserve must hold at least this many base units
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct StepOracle {
+    pub price_account: Pubkey, // Pyth PriceUpdateV2, passed in remaining_accounts
+    pub inverted: bool, // The leg sells the feed's quote token for its base token
+    pub input_decimals: u8,
+    pub output_decimals: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SplitLeg {
+    pub dex: DexType,
+    pub program_id: Pubkey,
+    pub weight_bps: u16, // Share of the leg's input; weights sum to 10000
+    pub route_data: Vec<u8>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum DexType {
+    Jupiter,
+    Raydium,
+    Orca,
+    Meteora,
+    Phoenix,
+    OpenBookV2,
+    Lifinity,
+    Saber,
+    Crema,
+    Invariant,
+    // Any registered program; see execute_custom_swap. It is trusted with the
+    // leg's input_amount, through a token delegate, and never with a signature.
+    Custom,
+}
+
+// 📒 ON-CHAIN STATE
+#[account]
+#[derive(InitSpace)]
+pub struct ProgramConfig {
+    #[max_len(MAX_ADMIN_SIGNERS)]
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub paused: bool,
+    pub fee_bps: u16,
+    pub min_route_len: u8,
+    pub max_route_len: u8,
+    pub min_compute_per_leg: u64,
+    pub cooldown_slots: u64,
+    pub max_cpis: u8,
+    pub oracle_max_deviation_bps: u16,
+    pub oracle_max_age_secs: u64,
+    pub max_oracle_staleness_slots: u64,
+    pub verbose_logs: bool,
+    pub check_residual_tokens: bool,
+    pub pool_snapshots: bool,
+    pub strategist: Option<Pubkey>,
+    pub strategist_bps: u16, // Share of profit after the protocol fee paid to the strategist
+    pub allow_self_sandwich: bool, // Skip the same-pool, opposite-direction leg check
+    pub max_route_data_len: u16, // Per-step (and per split portion) route_data limit in bytes
+    pub meter_legs: bool, // Emit LegCompute after every leg
+    pub allow_repeated_mints: bool, // Skip the figure-eight route check
+    pub breaker_reporter: Option<Pubkey>, // Key allowed to report execution outcomes
+    pub breaker_threshold: u16, // Consecutive reported failures that pause the program (0 = off)
+    pub consecutive_failures: u16,
+    pub max_oracle_confidence_bps: u16, // Widest Pyth confidence band, relative to price, an oracle leg accepts
+    #[max_len(MAX_ADMIN_SIGNERS)]
+    pub pending_signers: Vec<Pubkey>, // Proposed admin set awaiting accept_authority (empty = none)
+    pub pending_threshold: u8,
+    pub max_distinct_dexes: u8, // Most distinct venues one route may touch (0 = off)
+    pub bump: u8,
+}
+
+// Per-user guard against re-entering execute_arbitrage_route mid-route
+#[account]
+#[derive(InitSpace)]
+pub struct ExecutionLock {
+    pub locked: bool,
+    pub bump: u8,
+}
+
+// Authority over a user's trading token accounts; routes sign as it by PDA seeds
+#[account]
+#[derive(InitSpace)]
+pub struct TradingVault {
+    pub owner: Pubkey,
+    pub bump: u8,
+}
+
+// Per-user lifetime totals across successful routes
+#[account]
+#[derive(InitSpace)]
+pub struct UserStats {
+    pub total_profit: u128,
+    pub executions: u64,
+    pub best_profit_bps: u64,
+    pub last_execution_slot: u64,
+    pub last_nonce: u64,
+    pub bump: u8,
+}
+
+impl UserStats {
+    pub fn record(&mut self, profit: u64, profit_bps: u64, slot: u64) {
+        self.total_profit = self.total_profit.saturating_add(profit as u128);
+        self.executions = self.executions.saturating_add(1);
+        self.best_profit_bps = self.best_profit_bps.max(profit_bps);
+        self.last_execution_slot = slot;
+    }
+
+    pub fn consume_nonce(&mut self, nonce: u64) -> Result<()> {
+        require!(nonce > self.last_nonce, ArbitrageError::NonceReused);
+        self.last_nonce = nonce;
+        Ok(())
+    }
+
+    pub fn cooldown_elapsed(&self, slot: u64, cooldown_slots: u64) -> bool {
+        self.executions == 0 || slot >= self.last_execution_slot.saturating_add(cooldown_slots)
+    }
+}
+
+// Where a partially executed route stopped, so the next call can pick it up
+#[account]
+#[derive(InitSpace)]
+pub struct RouteCheckpoint {
+    pub active: bool,
+    pub route_hash: [u8; 32],
+    pub next_step: u8,
+    pub current_amount: u64,
+    pub spent: u64, // Net amount earlier calls took out of user_token_account
+    pub trade_amount: u64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct DexRegistry {
+    #[max_len(MAX_REGISTERED_DEXES)]
+    pub entries: Vec<DexEntry>,
+    pub bump: u8,
+}
+
+impl DexRegistry {
+    pub fn is_allowed(&self, dex: DexType, program_id: Pubkey) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.dex == dex && entry.program_id == program_id)
+    }
+
+    pub fn fee_bps(&self, dex: DexType, program_id: Pubkey) -> u16 {
+        self.entries
+            .iter()
+            .find(|entry| entry.dex == dex && entry.program_id == program_id)
+            .map_or(0, |entry| entry.fee_bps)
+    }
+
+    // A split leg pays each portion's venue fee on that portion's share of the input
+    pub fn step_fee_bps(&self, step: &SwapStep) -> u16 {
+        match &step.split {
+            Some(split) => {
+                let weighted: u32 = split
+                    .iter()
+                    .map(|leg| self.fee_bps(leg.dex, leg.program_id) as u32 * leg.weight_bps as u32)
+                    .sum();
+                (weighted / 10000) as u16
+            }
+            None => self.fee_bps(step.dex, step.program_id),
+        }
+    }
+
+    pub fn max_slippage_bps(&self, dex: DexType, program_id: Pubkey) -> u16 {
+        self.entries
+            .iter()
+            .find(|entry| entry.dex == dex && entry.program_id == program_id)
+            .map_or(10000, |entry| entry.max_slippage_bps)
+    }
+
+    pub fn program_id_for(&self, dex: DexType) -> Option<Pubkey> {
+        self.entries
+            .iter()
+            .find(|entry| entry.dex == dex)
+            .map(|entry| entry.program_id)
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct MintAllowlist {
+    #[max_len(MAX_ALLOWED_MINTS)]
+    pub mints: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl MintAllowlist {
+    pub fn is_allowed(&self, mint: Pubkey) -> bool {
+        self.mints.contains(&mint)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub struct DexEntry {
+    pub dex: DexType,
+    pub program_id: Pubkey,
+    pub fee_bps: u16,
+    pub max_slippage_bps: u16, // Caps every leg's slippage tolerance on this venue
+}
+
+pub struct RouteProgress {
+    pub next_step: usize,
+    pub current_amount: u64,
+    pub spent: u64,
+    pub trade_amount: u64,
+}
+
+pub struct RouteAccounts<'info> {
+    pub inputs: Vec<Option<&'info AccountInfo<'info>>>, // Set only for steps with an input_account
+    pub outputs: Vec<&'info AccountInfo<'info>>,
+    pub dex: Vec<Vec<AccountInfo<'info>>>,
+    pub tail: &'info [AccountInfo<'info>], // Accounts after the last step's DEX accounts
+}
+
+pub struct StepAccounts<'a, 'info> {
+    pub input: &'a AccountInfo<'info>,
+    pub output: &'a AccountInfo<'info>,
+    pub dex: &'a [AccountInfo<'info>],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ArbitrageOutcome {
+    pub profit: u64,
+    pub profit_bps: u64,
+    pub steps: u8,
+}
+
+#[derive(Debug)]
+pub struct SwapResult {
+    pub success: bool,
+    pub output_amount: u64, // Spendable amount received
+    pub slippage: Slippage,
+    pub transfer_fee: u64, // Token-2022 fee withheld out of what the DEX delivered
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TokenBalance {
+    pub amount: u64,
+    pub withheld_fee: u64,
+}
+
+impl TokenBalance {
+    pub fn received_since(&self, before: &TokenBalance) -> ReceivedAmount {
+        ReceivedAmount {
+            amount: self.amount.saturating_sub(before.amount),
+            transfer_fee: self.withheld_fee.saturating_sub(before.withheld_fee),
+        }
+    }
+}
+
+// A balance delta: what landed in the account and the transfer fee withheld on the way
+#[derive(Clone, Copy, Debug)]
+pub struct ReceivedAmount {
+    pub amount: u64,
+    pub transfer_fee: u64,
+}
+
+impl ReceivedAmount {
+    // What the DEX actually delivered, before the mint's transfer fee
+    pub fn delivered(&self) -> u64 {
+        self.amount.saturating_add(self.transfer_fee)
+    }
+}
+
+// Slippage magnitude capped at u16::MAX bps; direction tells a good fill from a bad one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slippage {
+    pub bps: u16,
+    pub direction: SlippageDirection,
+}
+
+// execute_arbitrage_batch's return value
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BatchOutcome {
+    pub profit: u64, // Net of protocol and strategist fees, summed over the routes that ran
+    pub profit_bps: u64, // Of the total amount traded
+    pub routes_executed: u8,
+    pub routes_skipped: u8,
+}
+
+// A pool's state as recorded for one leg of simulate_against_snapshot
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PoolStateSnapshot {
+    pub reserve_in: u64, // Pool balance of the leg's input_mint
+    pub reserve_out: u64, // Pool balance of the leg's output_mint
+    pub fee_bps: u16, // Swap fee taken off the input
+    pub amp: Option<u64>, // Stable-swap amplification; None for a constant-product pool
+}
+
+// simulate_arbitrage's return value, all in token_mint units
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct NetProfitPreview {
+    pub gross: u64,
+    pub fees: u64, // Protocol and strategist fees plus the estimated transaction fee
+    pub tip: u64,
+    pub net: u64,
+}
+
+impl NetProfitPreview {
+    // Fees are taken the way settlement takes them: the strategist's share comes
+    // out of what the protocol fee leaves
+    pub fn new(gross: u64, config: &ProgramConfig, tip_amount: u64, tx_fee_amount: u64) -> Result<Self> {
+        let protocol_fee = calculate_bps_share(gross, config.fee_bps)?;
+        let strategist_fee = calculate_bps_share(gross - protocol_fee, config.strategist_bps)?;
+        let fees = protocol_fee.saturating_add(strategist_fee).saturating_add(tx_fee_amount);
+        Ok(Self {
+            gross,
+            fees,
+            tip: tip_amount,
+            net: gross.saturating_sub(fees).saturating_sub(tip_amount),
+        })
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouteDirection {
+    Forward,
+    Reverse,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlippageDirection {
+    Unfavorable, // Filled below expectation
+    Exact,
+    Favorable, // Filled above expectation (positive slippage)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbortReason {
+    SlippageExceeded, // A leg delivered below its minimum output
+    ProfitLostToSlippage, // Quoted profit cleared the bar, realized profit didn't
+    InsufficientProfit, // Not profitable enough even as quoted
+}
+
+// 📻 EVENTS
+#[event]
+pub struct ArbitrageExecuted {
+    pub user: Pubkey,
+    pub start_amount: u64,
+    pub trade_amount: u64,
+    pub final_amount: u64,
+    pub profit: u64,
+    pub profit_bps: u64, // Saturates at u64::MAX; see calculate_profit_bps
+    pub protocol_fee: u64,
+    pub strategist_fee: u64,
+    pub steps: u8,
+    pub route_hash: [u8; 32], // sha256 of the borsh-serialized route
+    pub compute_used: u64, // Compute units the run consumed (0 when unavailable)
+    pub profit_usd: u64, // profit in micro-dollars at usd_price_feed (0 without a feed)
+}
+
+#[event]
+pub struct SwapStepExecuted {
+    pub step_index: u8,
+    pub dex: DexType,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub input_amount: u64,
+    pub output_amount: u64,
+    pub slippage_bps: u16,
+    pub slippage_direction: SlippageDirection,
+    pub transfer_fee: u64,
+}
+
+// A leg's pool vault balances just before or after its swap
+#[event]
+pub struct PoolSnapshot {
+    pub step_index: u8,
+    pub reserve_a: u64, // Vault holding the leg's input_mint
+    pub reserve_b: u64, // Vault holding the leg's output_mint
+    pub post_swap: bool,
+}
+
+// Compute units one leg's swap consumed, quote and CPI included
+#[event]
+pub struct LegCompute {
+    pub step_index: u8,
+    pub units: u64,
+}
+
+// Logged for any error inside a leg, since error codes can't say which leg failed
+#[event]
+pub struct ArbitrageFailed {
+    pub step_index: u8,
+    pub error_code: u32,
+}
+
+// Logged just before a route is rejected, so monitors can see why opportunities are missed
+#[event]
+pub struct ArbitrageAborted {
+    pub reason: AbortReason,
+    pub realized_profit_bps: u64,
+    pub expected_profit_bps: u64,
+}
+
+// Logged for each batched route left out because its quotes fell short
+#[event]
+pub struct RouteSkipped {
+    pub route_index: u8,
+    pub expected_profit_bps: u64,
+}
+
+#[event]
+pub struct CircuitBreakerTripped {
+    pub consecutive_failures: u16,
+}
+
+#[event]
+pub struct FeesWithdrawn {
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct AuthorityAccepted {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+// ❌ ERROR HANDLING
+#[error_code]
+pub enum ArbitrageError {
+    #[msg("Route has fewer steps than the configured minimum")]
+    RouteTooShort,
+    #[msg("Route has more steps than the configured maximum")]
+    RouteTooLong,
+    #[msg("Invalid minimum profit specified")]
+    InvalidMinProfit,
+    #[msg("Swap execution failed")]
+    SwapFailed,
+    #[msg("Insufficient profit achieved")]
+    InsufficientProfit,
+    #[msg("Slippage exceeded maximum")]
+    SlippageExceeded,
+    #[msg("Missing token account for a route step")]
+    MissingTokenAccount,
+    #[msg("Account is not a valid SPL token account")]
+    InvalidTokenAccount,
+    #[msg("Token account owner or mint does not match the route step")]
+    TokenAccountMismatch,
+    #[msg("Starting token balance is zero")]
+    ZeroStartBalance,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Route steps do not form a continuous chain")]
+    DiscontinuousRoute,
+    #[msg("Route must end in the mint it started with")]
+    RouteNotCyclic,
+    #[msg("Route deadline slot has passed")]
+    DeadlineExceeded,
+    #[msg("DEX program is not in the registry")]
+    DexNotWhitelisted,
+    #[msg("DEX program is already registered")]
+    DexAlreadyRegistered,
+    #[msg("DEX program is not registered")]
+    DexNotRegistered,
+    #[msg("DEX registry is full")]
+    DexRegistryFull,
+    #[msg("Not enough admin signers approved this instruction")]
+    Unauthorized,
+    #[msg("Missing DEX accounts for a route step")]
+    MissingDexAccounts,
+    #[msg("DEX program account does not match the route step")]
+    DexProgramMismatch,
+    #[msg("Pool account data is invalid")]
+    InvalidPoolAccount,
+    #[msg("Pool mints do not match the route step")]
+    PoolMintMismatch,
+    #[msg("Route data is invalid for this DEX")]
+    InvalidRouteData,
+    #[msg("Slippage must be at most 10000 bps")]
+    InvalidSlippage,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("Fee must be at most 10000 bps")]
+    InvalidFee,
+    #[msg("Missing flash loan accounts")]
+    MissingFlashLoanAccounts,
+    #[msg("Route proceeds do not cover the flash loan repayment")]
+    FlashRepayFailed,
+    #[msg("Jito tip account is required for a nonzero tip")]
+    MissingTipAccount,
+    #[msg("Invalid route length bounds")]
+    InvalidRouteBounds,
+    #[msg("Account index is out of range")]
+    InvalidAccountIndex,
+    #[msg("Native mint and associated token program are required to wrap SOL")]
+    MissingWrapAccounts,
+    #[msg("Wrapping SOL requires the legacy token program")]
+    InvalidWrapSetup,
+    #[msg("Route execution is already in progress for this user")]
+    ReentrancyDetected,
+    #[msg("Not enough compute left to finish the next leg")]
+    ComputeExhausted,
+    #[msg("Split weights must be positive and sum to 10000 bps")]
+    InvalidSplit,
+    #[msg("wrap_sol cannot be combined with dry_run")]
+    DryRunUnsupported,
+    #[msg("Remainder DEX must differ from the primary and cannot be combined with a split")]
+    InvalidRemainderVenue,
+    #[msg("Division by zero")]
+    DivisionByZero,
+    #[msg("Route was quoted profitable but slippage ate the profit")]
+    ProfitLostToSlippage,
+    #[msg("Too many DEX entries in one batch")]
+    DexBatchTooLarge,
+    #[msg("Exact output is only supported on a plain Raydium or Orca final leg")]
+    InvalidExactOutput,
+    #[msg("User executed too recently; cooldown still active")]
+    CooldownActive,
+    #[msg("token_mint does not match the route's first input mint")]
+    MintMismatch,
+    #[msg("Admin signers must be 1..=5 distinct keys with a threshold between 1 and their count")]
+    InvalidMultisig,
+    #[msg("Oracle account is missing, unverified or malformed")]
+    InvalidOracle,
+    #[msg("Oracle price is too old")]
+    StaleOracle,
+    #[msg("Leg filled further below the oracle price than allowed")]
+    OracleDeviationExceeded,
+    #[msg("max_steps requires a route checkpoint account")]
+    MissingCheckpoint,
+    #[msg("Flash loans cannot span a checkpointed route")]
+    CheckpointUnsupported,
+    #[msg("Route does not match the checkpointed route")]
+    CheckpointRouteMismatch,
+    #[msg("Step expected_rate must be nonzero")]
+    InvalidRate,
+    #[msg("Pool reserves are below the minimum liquidity")]
+    InsufficientLiquidity,
+    #[msg("Unsupported swap step version")]
+    UnsupportedStepVersion,
+    #[msg("Route would make more CPIs than the configured maximum")]
+    TooManyCpis,
+    #[msg("Tick or bin arrays do not cover the pool's current price")]
+    InvalidTickArrays,
+    #[msg("Batch contains no routes")]
+    EmptyBatch,
+    #[msg("Batch has more steps in total than allowed")]
+    BatchTooLarge,
+    #[msg("Route trades a mint that is not on the allowlist")]
+    MintNotAllowed,
+    #[msg("Mint is already on the allowlist")]
+    MintAlreadyAllowed,
+    #[msg("Mint allowlist is full")]
+    MintAllowlistFull,
+    #[msg("Route step received zero input")]
+    ZeroStepInput,
+    #[msg("Step expected_output disagrees with its expected_rate")]
+    ExpectedOutputMismatch,
+    #[msg("wrap_sol and flash loans cannot be used when trading from a vault")]
+    VaultUnsupported,
+    #[msg("Nonce is not greater than the last one used")]
+    NonceReused,
+    #[msg("Route left tokens behind in an intermediate account")]
+    ResidualTokens,
+    #[msg("Strategist share is due but the strategist token account is missing or not theirs")]
+    MissingStrategistAccount,
+    #[msg("Two legs trade the same pool in opposite directions")]
+    SelfSandwichDetected,
+    #[msg("Step route_data exceeds the configured size limit")]
+    RouteDataTooLarge,
+    #[msg("Expected exactly one pool snapshot per route step")]
+    SnapshotMismatch,
+    #[msg("Route passes through the same mint more than once")]
+    RepeatedMint,
+    #[msg("Signer is not the configured circuit breaker reporter")]
+    InvalidBreakerReporter,
+    #[msg("remaining_accounts is too short for the route's legs")]
+    MissingAccounts,
+    #[msg("Oracle confidence interval is wider than the configured maximum")]
+    OracleConfidenceTooWide,
+    #[msg("No admin signer set has been proposed")]
+    NoPendingAuthority,
+    #[msg("Route touches more distinct DEXes than the configured maximum")]
+    TooManyDexes,
+    #[msg("Transaction does not bracket the route with a matching Solend flash borrow and repay")]
+    FlashLoanMismatch,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use spl_token_2022::state::{Account as SplTokenAccount, AccountState};
+
+    fn encode<T: AnchorSerialize>(value: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        value.serialize(&mut data).unwrap();
+        data
+    }
+
+    fn step(input_mint: Pubkey, output_mint: Pubkey, expected_rate: u64) -> SwapStep {
+        SwapStepV1 {
+            input_mint,
+            output_mint,
+            dex: DexType::Raydium,
+            program_id: Pubkey::new_from_array([7; 32]),
+            expected_rate,
+            route_data: vec![1, 2, 3],
+        }
+        .into()
+    }
+
+    fn cycle() -> Vec<SwapStep> {
+        let (a, b) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        vec![step(a, b, 2_000), step(b, a, 501)]
+    }
+
+    // What initialize_config sets up, with one admin
+    fn config() -> ProgramConfig {
+        ProgramConfig {
+            signers: vec![Pubkey::new_from_array([9; 32])],
+            threshold: 1,
+            paused: false,
+            fee_bps: 0,
+            min_route_len: DEFAULT_MIN_ROUTE_LEN,
+            max_route_len: DEFAULT_MAX_ROUTE_LEN,
+            min_compute_per_leg: DEFAULT_MIN_COMPUTE_PER_LEG,
+            cooldown_slots: 0,
+            max_cpis: 0,
+            oracle_max_deviation_bps: DEFAULT_ORACLE_MAX_DEVIATION_BPS,
+            oracle_max_age_secs: DEFAULT_ORACLE_MAX_AGE_SECS,
+            max_oracle_staleness_slots: DEFAULT_MAX_ORACLE_STALENESS_SLOTS,
+            verbose_logs: false,
+            check_residual_tokens: true,
+            pool_snapshots: false,
+            strategist: None,
+            strategist_bps: 0,
+            allow_self_sandwich: false,
+            max_route_data_len: DEFAULT_MAX_ROUTE_DATA_LEN,
+            meter_legs: false,
+            allow_repeated_mints: false,
+            breaker_reporter: None,
+            breaker_threshold: 0,
+            consecutive_failures: 0,
+            max_oracle_confidence_bps: DEFAULT_MAX_ORACLE_CONFIDENCE_BPS,
+            pending_signers: Vec::new(),
+            pending_threshold: 0,
+            max_distinct_dexes: 0,
+            bump: 255,
+        }
+    }
+
+    fn user_stats() -> UserStats {
+        UserStats { total_profit: 0, executions: 0, best_profit_bps: 0, last_execution_slot: 0, last_nonce: 0, bump: 255 }
+    }
+
+    fn on_dex(step: SwapStep, dex: DexType) -> SwapStep {
+        SwapStep { dex, ..step }
+    }
+
+    fn split_leg(dex: DexType, weight_bps: u16) -> SplitLeg {
+        SplitLeg { dex, program_id: Pubkey::new_unique(), weight_bps, route_data: vec![4, 5] }
+    }
+
+    // Leaked so tests can hold AccountInfo<'static> like the runtime hands out
+    fn account_info(key: Pubkey, is_signer: bool, owner: Pubkey, data: Vec<u8>) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            is_signer,
+            true,
+            Box::leak(Box::new(0)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            false,
+            0,
+        )
+    }
+
+    fn signer(key: Pubkey) -> AccountInfo<'static> {
+        account_info(key, true, Pubkey::default(), Vec::new())
+    }
+
+    fn update_config_accounts(config: &ProgramConfig) -> UpdateConfig<'static> {
+        let mut data = ProgramConfig::DISCRIMINATOR.to_vec();
+        config.serialize(&mut data).unwrap();
+        let info = Box::leak(Box::new(account_info(Pubkey::new_unique(), false, crate::ID, data)));
+        UpdateConfig { config: Account::try_from(&*info).unwrap() }
+    }
+
+    fn token_account(key: Pubkey, mint: Pubkey, amount: u64) -> AccountInfo<'static> {
+        let info = account_info(key, false, token::ID, vec![0; SplTokenAccount::LEN]);
+        set_token_amount(&info, mint, amount);
+        info
+    }
+
+    fn set_token_amount(info: &AccountInfo, mint: Pubkey, amount: u64) {
+        let account = SplTokenAccount { mint, amount, state: AccountState::Initialized, ..Default::default() };
+        SplTokenAccount::pack(account, &mut info.try_borrow_mut_data().unwrap()).unwrap();
+    }
+
+    fn balances<'a, 'info>(accounts: &[&'a AccountInfo<'info>]) -> Vec<(&'a AccountInfo<'info>, u64)> {
+        accounts.iter().map(|info| (*info, load_token_account(info).unwrap().amount)).collect()
+    }
+
+    #[test]
+    fn continuous_route_chains_each_output_into_the_next_input() {
+        let (usdc, sol, bonk) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let route = vec![step(usdc, sol, 1_000), step(sol, bonk, 1_000), step(bonk, usdc, 1_000)];
+        assert!(validate_route_continuity(&route, usdc).is_ok());
+    }
+
+    #[test]
+    fn broken_route_chain_is_rejected() {
+        let (usdc, sol, bonk) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        // The second leg spends BONK the first never produced
+        let route = vec![step(usdc, sol, 1_000), step(bonk, usdc, 1_000)];
+        assert_eq!(
+            validate_route_continuity(&route, usdc).unwrap_err(),
+            ArbitrageError::DiscontinuousRoute.into()
+        );
+        // A continuous route that doesn't start from token_mint
+        let route = vec![step(sol, usdc, 1_000), step(usdc, sol, 1_000)];
+        assert_eq!(validate_route_continuity(&route, usdc).unwrap_err(), ArbitrageError::MintMismatch.into());
+        assert_eq!(validate_route_continuity(&[], usdc).unwrap_err(), ArbitrageError::RouteTooShort.into());
+    }
+
+    #[test]
+    fn open_path_is_not_a_cycle() {
+        let (usdc, sol, bonk) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let open = vec![step(usdc, sol, 1_000), step(sol, bonk, 1_000)];
+        assert!(validate_route_continuity(&open, usdc).is_ok());
+        assert_eq!(validate_route_cycle(&open).unwrap_err(), ArbitrageError::RouteNotCyclic.into());
+
+        let closed = vec![step(usdc, sol, 1_000), step(sol, bonk, 1_000), step(bonk, usdc, 1_000)];
+        assert!(validate_route_cycle(&closed).is_ok());
+    }
+
+    #[test]
+    fn figure_eight_route_is_rejected_unless_allowed() {
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let figure_eight = vec![step(a, b, 1_000), step(b, a, 1_000), step(a, c, 1_000), step(c, a, 1_000)];
+        let mut config = config();
+        assert!(validate_route_continuity(&figure_eight, a).is_ok());
+        assert!(validate_route_cycle(&figure_eight).is_ok());
+        assert_eq!(
+            validate_no_repeated_mints(&figure_eight, &config).unwrap_err(),
+            ArbitrageError::RepeatedMint.into()
+        );
+        // Coming back to the start mint at the end is the cycle itself, not a repeat
+        let triangle = vec![step(a, b, 1_000), step(b, c, 1_000), step(c, a, 1_000)];
+        assert!(validate_no_repeated_mints(&triangle, &config).is_ok());
+
+        config.allow_repeated_mints = true;
+        assert!(validate_no_repeated_mints(&figure_eight, &config).is_ok());
+    }
+
+    #[test]
+    fn four_dexes_exceed_a_cap_of_three() {
+        let (a, b, c, d) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let route = vec![
+            on_dex(step(a, b, 1_000), DexType::Raydium),
+            on_dex(step(b, c, 1_000), DexType::Orca),
+            on_dex(step(c, d, 1_000), DexType::Meteora),
+            on_dex(step(d, a, 1_000), DexType::Phoenix),
+        ];
+        let mut config = config();
+        // 0 leaves the cap off
+        assert!(validate_distinct_dexes(&route, &config).is_ok());
+        config.max_distinct_dexes = 3;
+        assert_eq!(validate_distinct_dexes(&route, &config).unwrap_err(), ArbitrageError::TooManyDexes.into());
+
+        // A venue used twice counts once
+        let mut repeated = route.clone();
+        repeated[3].dex = DexType::Raydium;
+        assert!(validate_distinct_dexes(&repeated, &config).is_ok());
+    }
+
+    #[test]
+    fn split_portions_and_remainder_venues_count_towards_the_cap() {
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = config();
+        config.max_distinct_dexes = 3;
+
+        // Raydium, then Orca/Meteora split, then Raydium back: three venues
+        let mut route = vec![step(a, b, 1_000), step(b, c, 1_000), step(c, a, 1_000)];
+        route[1].split = Some(vec![split_leg(DexType::Orca, 6_000), split_leg(DexType::Meteora, 4_000)]);
+        assert!(validate_distinct_dexes(&route, &config).is_ok());
+
+        // A Lifinity remainder venue on the last leg makes four
+        route[2].remainder_dex = Some(DexType::Lifinity);
+        assert_eq!(validate_distinct_dexes(&route, &config).unwrap_err(), ArbitrageError::TooManyDexes.into());
+    }
+
+    #[test]
+    fn oversized_route_data_is_rejected() {
+        let mut config = config();
+        config.max_route_data_len = 16;
+        let mut route = cycle();
+        route[0].route_data = vec![0; 16];
+        assert!(validate_route_data_size(&route, &config).is_ok());
+
+        route[1].route_data = vec![0; 17];
+        assert_eq!(validate_route_data_size(&route, &config).unwrap_err(), ArbitrageError::RouteDataTooLarge.into());
+    }
+
+    #[test]
+    fn oversized_split_portion_route_data_is_rejected() {
+        let mut config = config();
+        config.max_route_data_len = 16;
+        let mut route = cycle();
+        let mut oversized = split_leg(DexType::Meteora, 4_000);
+        oversized.route_data = vec![0; 17];
+        route[0].split = Some(vec![split_leg(DexType::Orca, 6_000), oversized]);
+        assert_eq!(validate_route_data_size(&route, &config).unwrap_err(), ArbitrageError::RouteDataTooLarge.into());
+    }
+
+    #[test]
+    fn step_parameters_reject_each_invalid_setting() {
+        fn with(edit: impl Fn(&mut Vec<SwapStep>)) -> Vec<SwapStep> {
+            let mut route = cycle();
+            edit(&mut route);
+            route
+        }
+        let cases: Vec<(&str, Vec<SwapStep>, ArbitrageError)> = vec![
+            ("zero rate", with(|route| route[0].expected_rate = 0), ArbitrageError::InvalidRate),
+            ("slippage over 100%", with(|route| route[1].slippage_bps = Some(10_001)), ArbitrageError::InvalidSlippage),
+            (
+                "single-venue split",
+                with(|route| route[0].split = Some(vec![split_leg(DexType::Orca, 10_000)])),
+                ArbitrageError::InvalidSplit,
+            ),
+            (
+                "zero-weight portion",
+                with(|route| {
+                    route[0].split = Some(vec![split_leg(DexType::Orca, 10_000), split_leg(DexType::Meteora, 0)])
+                }),
+                ArbitrageError::InvalidSplit,
+            ),
+            (
+                "weights short of 10000",
+                with(|route| {
+                    route[0].split = Some(vec![split_leg(DexType::Orca, 6_000), split_leg(DexType::Meteora, 3_999)])
+                }),
+                ArbitrageError::InvalidSplit,
+            ),
+            (
+                "remainder on the primary's own venue",
+                with(|route| route[0].remainder_dex = Some(DexType::Raydium)),
+                ArbitrageError::InvalidRemainderVenue,
+            ),
+            (
+                "remainder on a split leg",
+                with(|route| {
+                    route[0].split = Some(vec![split_leg(DexType::Orca, 6_000), split_leg(DexType::Meteora, 4_000)]);
+                    route[0].remainder_dex = Some(DexType::Lifinity);
+                }),
+                ArbitrageError::InvalidRemainderVenue,
+            ),
+            (
+                "exact output before the last leg",
+                with(|route| route[0].exact_output = Some(1_000)),
+                ArbitrageError::InvalidExactOutput,
+            ),
+            (
+                "exact output without a base-out swap",
+                with(|route| {
+                    route[1].dex = DexType::Meteora;
+                    route[1].exact_output = Some(1_000);
+                }),
+                ArbitrageError::InvalidExactOutput,
+            ),
+            (
+                "exact output with a remainder venue",
+                with(|route| {
+                    route[1].exact_output = Some(1_000);
+                    route[1].remainder_dex = Some(DexType::Orca);
+                }),
+                ArbitrageError::InvalidExactOutput,
+            ),
+        ];
+        for (case, route, expected) in cases {
+            assert_eq!(validate_step_parameters(&route).err(), Some(expected.into()), "{}", case);
+        }
+    }
+
+    #[test]
+    fn step_parameters_accept_valid_settings() {
+        let mut route = cycle();
+        route[0].slippage_bps = Some(10_000);
+        route[0].split = Some(vec![split_leg(DexType::Orca, 6_000), split_leg(DexType::Meteora, 4_000)]);
+        route[1].dex = DexType::Orca;
+        route[1].exact_output = Some(1_000);
+        assert!(validate_step_parameters(&route).is_ok());
+
+        let mut route = cycle();
+        route[0].remainder_dex = Some(DexType::Orca);
+        assert!(validate_step_parameters(&route).is_ok());
+    }
+
+    #[test]
+    fn same_nonce_twice_is_rejected() {
+        let mut stats = user_stats();
+        assert!(stats.consume_nonce(7).is_ok());
+        assert_eq!(stats.consume_nonce(7).unwrap_err(), ArbitrageError::NonceReused.into());
+        assert_eq!(stats.last_nonce, 7);
+    }
+
+    #[test]
+    fn lower_nonce_is_rejected() {
+        let mut stats = user_stats();
+        stats.consume_nonce(7).unwrap();
+        assert_eq!(stats.consume_nonce(6).unwrap_err(), ArbitrageError::NonceReused.into());
+        // 0 is never accepted: it's what a fresh account starts from
+        assert_eq!(user_stats().consume_nonce(0).unwrap_err(), ArbitrageError::NonceReused.into());
+        // Gaps are fine; nonces only have to increase
+        assert!(stats.consume_nonce(100).is_ok());
+        assert_eq!(stats.last_nonce, 100);
+    }
+
+    #[test]
+    fn second_execution_within_the_cooldown_is_throttled() {
+        let mut stats = user_stats();
+        // A user who never executed is never throttled
+        assert!(stats.cooldown_elapsed(0, 50));
+
+        stats.record(10, 5, 1_000);
+        assert!(!stats.cooldown_elapsed(1_000, 50));
+        assert!(!stats.cooldown_elapsed(1_049, 50));
+        assert!(stats.cooldown_elapsed(1_050, 50));
+        // A zero cooldown turns the throttle off
+        assert!(stats.cooldown_elapsed(1_000, 0));
+    }
+
+    #[test]
+    fn admin_signer_sets_must_be_a_valid_threshold_of_distinct_keys() {
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(validate_admin_signers(&[a, b, c], 2).is_ok());
+        assert!(validate_admin_signers(&[a], 1).is_ok());
+        for (signers, threshold) in [(vec![], 1), (vec![a, b], 0), (vec![a, b], 3), (vec![a, b, a], 2)] {
+            assert_eq!(
+                validate_admin_signers(&signers, threshold).unwrap_err(),
+                ArbitrageError::InvalidMultisig.into()
+            );
+        }
+        let too_many: Vec<Pubkey> = (0..=MAX_ADMIN_SIGNERS).map(|_| Pubkey::new_unique()).collect();
+        assert_eq!(validate_admin_signers(&too_many, 1).unwrap_err(), ArbitrageError::InvalidMultisig.into());
+    }
+
+    #[test]
+    fn admin_approval_needs_the_threshold_of_configured_signers() {
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = config();
+        config.signers = vec![a, b, c];
+        config.threshold = 2;
+
+        // Two of three signed
+        assert!(require_admin_approval(&config, &[signer(a), signer(c)]).is_ok());
+        // Only one did
+        assert_eq!(require_admin_approval(&config, &[signer(b)]).unwrap_err(), ArbitrageError::Unauthorized.into());
+        // Passing the same signer twice still counts once
+        assert_eq!(
+            require_admin_approval(&config, &[signer(a), signer(a)]).unwrap_err(),
+            ArbitrageError::Unauthorized.into()
+        );
+        // A configured key that didn't sign, or a signer that isn't configured, doesn't count
+        let unsigned_b = account_info(b, false, Pubkey::default(), Vec::new());
+        assert_eq!(
+            require_admin_approval(&config, &[signer(a), unsigned_b, signer(Pubkey::new_unique())]).unwrap_err(),
+            ArbitrageError::Unauthorized.into()
+        );
+    }
+
+    #[test]
+    fn authority_transfer_proposal_needs_admin_approval() {
+        let admin = config().signers[0];
+        let (new_a, new_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut accounts = update_config_accounts(&config());
+
+        let outsider = [signer(Pubkey::new_unique())];
+        let result = graph_arbitrage::propose_authority(
+            Context::new(&crate::ID, &mut accounts, &outsider, UpdateConfigBumps {}),
+            vec![new_a, new_b],
+            2,
+        );
+        assert_eq!(result.unwrap_err(), ArbitrageError::Unauthorized.into());
+
+        let admins = [signer(admin)];
+        graph_arbitrage::propose_authority(
+            Context::new(&crate::ID, &mut accounts, &admins, UpdateConfigBumps {}),
+            vec![new_a, new_b],
+            2,
+        )
+        .unwrap();
+        assert_eq!(accounts.config.pending_signers, vec![new_a, new_b]);
+        assert_eq!(accounts.config.pending_threshold, 2);
+        // Nothing changes until the proposed set accepts
+        assert_eq!(accounts.config.signers, vec![admin]);
+        assert_eq!(accounts.config.threshold, 1);
+    }
+
+    #[test]
+    fn authority_transfer_takes_effect_once_every_proposed_signer_accepts() {
+        let (new_a, new_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut proposed = config();
+        proposed.pending_signers = vec![new_a, new_b];
+        proposed.pending_threshold = 2;
+        let mut accounts = update_config_accounts(&proposed);
+
+        let both = [signer(new_b), signer(new_a)];
+        graph_arbitrage::accept_authority(Context::new(&crate::ID, &mut accounts, &both, UpdateConfigBumps {})).unwrap();
+        assert_eq!(accounts.config.signers, vec![new_a, new_b]);
+        assert_eq!(accounts.config.threshold, 2);
+        assert!(accounts.config.pending_signers.is_empty());
+        assert_eq!(accounts.config.pending_threshold, 0);
+
+        // Nothing is pending any more, so accepting again fails
+        let result = graph_arbitrage::accept_authority(Context::new(&crate::ID, &mut accounts, &both, UpdateConfigBumps {}));
+        assert_eq!(result.unwrap_err(), ArbitrageError::NoPendingAuthority.into());
+    }
+
+    #[test]
+    fn authority_transfer_rejects_an_unauthorized_accept() {
+        let admin = config().signers[0];
+        let (new_a, new_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut proposed = config();
+        proposed.pending_signers = vec![new_a, new_b];
+        proposed.pending_threshold = 1;
+        let mut accounts = update_config_accounts(&proposed);
+
+        // Meeting the pending threshold isn't enough; every proposed key must sign.
+        // Neither is the current admin signing on the new set's behalf.
+        let unsigned_b = account_info(new_b, false, Pubkey::default(), Vec::new());
+        for remaining in [vec![signer(new_a)], vec![signer(new_a), unsigned_b], vec![signer(admin), signer(new_a)]] {
+            let result = graph_arbitrage::accept_authority(Context::new(&crate::ID, &mut accounts, &remaining, UpdateConfigBumps {}));
+            assert_eq!(result.unwrap_err(), ArbitrageError::Unauthorized.into());
+        }
+        assert_eq!(accounts.config.signers, vec![admin]);
+        assert_eq!(accounts.config.pending_signers, vec![new_a, new_b]);
+    }
+
+    #[test]
+    fn slippage_below_expected_is_unfavorable() {
+        let slippage = calculate_slippage(10_000, 9_950);
+        assert_eq!(slippage, Slippage { bps: 50, direction: SlippageDirection::Unfavorable });
+    }
+
+    #[test]
+    fn slippage_at_expected_is_exact() {
+        let slippage = calculate_slippage(10_000, 10_000);
+        assert_eq!(slippage, Slippage { bps: 0, direction: SlippageDirection::Exact });
+    }
+
+    #[test]
+    fn slippage_above_expected_is_favorable() {
+        let slippage = calculate_slippage(10_000, 10_125);
+        assert_eq!(slippage, Slippage { bps: 125, direction: SlippageDirection::Favorable });
+    }
+
+    #[test]
+    fn slippage_saturates_instead_of_failing() {
+        assert_eq!(calculate_slippage(1, u64::MAX).bps, u16::MAX);
+        assert_eq!(calculate_slippage(0, 5).bps, 0);
+    }
+
+    #[test]
+    fn profit_bps_saturates_when_a_tiny_input_returns_a_fortune() {
+        assert_eq!(calculate_profit_bps(25, 10_000).unwrap(), 25);
+        assert_eq!(calculate_profit_bps(u64::MAX, 1).unwrap(), u64::MAX);
+        assert_eq!(calculate_profit_bps(u64::MAX / 10_000 + 1, 1).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn residual_bonk_left_in_an_intermediate_account_aborts_the_route() {
+        let (usdc, bonk, sol) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let route = vec![step(usdc, bonk, 1_000), step(bonk, sol, 1_000), step(sol, usdc, 1_000)];
+        let user_usdc = token_account(Pubkey::new_unique(), usdc, 1_000);
+        let bonk_account = token_account(Pubkey::new_unique(), bonk, 0);
+        let sol_account = token_account(Pubkey::new_unique(), sol, 0);
+        let outputs = [&bonk_account, &sol_account, &user_usdc];
+
+        let checked = residual_checked_accounts(&route, &outputs, user_usdc.key());
+        assert_eq!(checked.iter().map(|info| info.key()).collect::<Vec<_>>(), vec![bonk_account.key(), sol_account.key()]);
+        let before = balances(&checked);
+
+        // The BONK -> SOL leg only spent part of what it was sent
+        set_token_amount(&bonk_account, bonk, 42);
+        assert_eq!(require_no_residual_tokens(&before).unwrap_err(), ArbitrageError::ResidualTokens.into());
+        set_token_amount(&bonk_account, bonk, 0);
+        assert!(require_no_residual_tokens(&before).is_ok());
+    }
+
+    #[test]
+    fn order_book_leg_mid_route_may_leave_lot_dust() {
+        let (usdc, sol, bonk) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let route = vec![
+            step(usdc, sol, 1_000),
+            on_dex(step(sol, bonk, 1_000), DexType::Phoenix),
+            step(bonk, usdc, 1_000),
+        ];
+        let user_usdc = token_account(Pubkey::new_unique(), usdc, 1_000);
+        let sol_account = token_account(Pubkey::new_unique(), sol, 0);
+        let bonk_account = token_account(Pubkey::new_unique(), bonk, 0);
+        let outputs = [&sol_account, &bonk_account, &user_usdc];
+
+        // The SOL account feeds the Phoenix leg, so only BONK is held to its starting balance
+        let checked = residual_checked_accounts(&route, &outputs, user_usdc.key());
+        assert_eq!(checked.iter().map(|info| info.key()).collect::<Vec<_>>(), vec![bonk_account.key()]);
+        let before = balances(&checked);
+        set_token_amount(&sol_account, sol, 3); // Less than a base lot
+        assert!(require_no_residual_tokens(&before).is_ok());
+
+        // The same holds for an OpenBook split portion or remainder venue
+        let mut split = step(sol, bonk, 1_000);
+        split.split = Some(vec![
+            SplitLeg { dex: DexType::Orca, program_id: Pubkey::new_unique(), weight_bps: 5_000, route_data: Vec::new() },
+            SplitLeg { dex: DexType::OpenBookV2, program_id: Pubkey::new_unique(), weight_bps: 5_000, route_data: Vec::new() },
+        ]);
+        assert!(keeps_unspent_input(&split));
+        let remainder = SwapStep { remainder_dex: Some(DexType::OpenBookV2), ..step(sol, bonk, 1_000) };
+        assert!(keeps_unspent_input(&remainder));
+        assert!(!keeps_unspent_input(&step(sol, bonk, 1_000)));
+    }
+
+    #[test]
+    fn order_book_min_output_scales_to_whole_lots() {
+        // 1_050 base at 100 per lot spends 10 lots; the 50 left over can't be sold
+        let aligned = lot_aligned(1_050, 100, 2_100).unwrap();
+        assert_eq!((aligned.lots, aligned.input_amount, aligned.min_output), (10, 1_000, 2_000));
+        // Rounded up, so scaling never loosens the bound
+        assert_eq!(lot_aligned(1_050, 100, 2_101).unwrap().min_output, 2_001);
+        let exact = lot_aligned(1_000, 100, 2_000).unwrap();
+        assert_eq!((exact.lots, exact.min_output), (10, 2_000));
+        assert_eq!(lot_aligned(99, 100, 200).err(), Some(ArbitrageError::InvalidRouteData.into()));
+    }
+
+    #[test]
+    fn order_book_partial_fill_falls_short_of_the_minimum() {
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let leg = on_dex(step(sol, usdc, 2_000), DexType::Phoenix);
+        let aligned = lot_aligned(1_050, 100, 2_100).unwrap();
+        let fill = |amount| swap_result(&leg, aligned.input_amount, aligned.min_output, &ReceivedAmount { amount, transfer_fee: 0 });
+
+        // All 10 lots filled: enough against the scaled minimum, short of the unscaled 2_100
+        assert!(fill(2_000).unwrap().success);
+        // 6 of 10 lots filled: the leg fails, and the route aborts as slippage
+        let partial = fill(1_200).unwrap();
+        assert!(!partial.success);
+        assert_eq!(partial.slippage.direction, SlippageDirection::Unfavorable);
+        assert_eq!(partial.slippage.bps, 4_000);
+    }
+
+    #[test]
+    fn flash_instruction_amount_reads_only_the_matching_tag() {
+        let mut data = vec![SOLEND_FLASH_REPAY_TAG];
+        data.extend_from_slice(&1_500u64.to_le_bytes());
+        data.push(3); // borrow instruction index
+        let repay = Instruction { program_id: SOLEND_PROGRAM_ID, accounts: Vec::new(), data };
+
+        assert_eq!(flash_instruction_amount(&repay, SOLEND_FLASH_REPAY_TAG), Some(1_500));
+        assert_eq!(flash_instruction_amount(&repay, SOLEND_FLASH_BORROW_TAG), None);
+        let truncated = Instruction { data: vec![SOLEND_FLASH_BORROW_TAG, 1, 2], ..repay };
+        assert_eq!(flash_instruction_amount(&truncated, SOLEND_FLASH_BORROW_TAG), None);
+    }
+
+    #[test]
+    fn route_hash_is_stable_and_tracks_every_step() {
+        let route = cycle();
+        assert_eq!(hash_route(&route).unwrap(), hash_route(&cycle()).unwrap());
+
+        let mut changed = cycle();
+        changed[1].expected_rate += 1;
+        assert_ne!(hash_route(&route).unwrap(), hash_route(&changed).unwrap());
+
+        let mut changed = cycle();
+        changed[0].remainder_dex = Some(DexType::Orca);
+        assert_ne!(hash_route(&route).unwrap(), hash_route(&changed).unwrap());
+    }
+
+    // Every field an older layout carries, set to something other than its default
+    fn v4_step() -> SwapStepV4 {
+        let pubkey = |byte| Pubkey::new_from_array([byte; 32]);
+        SwapStepV4 {
+            input_mint: pubkey(1),
+            output_mint: pubkey(2),
+            dex: DexType::Orca,
+            program_id: pubkey(3),
+            expected_rate: 1_234,
+            route_data: vec![9, 8, 7],
+            slippage_bps: Some(25),
+            account_indices: Some(vec![4, 5]),
+            split: None,
+            remainder_dex: Some(DexType::Raydium),
+            exact_output: Some(77),
+            oracle: Some(StepOracle { price_account: pubkey(4), inverted: true, input_decimals: 9, output_decimals: 6 }),
+            liquidity: Some(PoolLiquidity { reserves: vec![pubkey(5)], min_pool_liquidity: 1_000 }),
+            live_reserves: Some(LiveReserves { input_vault: pubkey(6), output_vault: pubkey(7) }),
+            expected_output: Some(4_321),
+        }
+    }
+
+    fn decoded<T: AnchorSerialize>(version: u8, step: &T) -> Vec<u8> {
+        encode(&VersionedSwapStep { version, data: encode(step) }.decode().unwrap())
+    }
+
+    #[test]
+    fn decodes_v1_with_every_later_field_unset() {
+        let v4 = v4_step();
+        let v1 = SwapStepV1 {
+            input_mint: v4.input_mint,
+            output_mint: v4.output_mint,
+            dex: v4.dex,
+            program_id: v4.program_id,
+            expected_rate: v4.expected_rate,
+            route_data: v4.route_data.clone(),
+        };
+        let expected = SwapStep::from(v1.clone());
+        assert!(expected.slippage_bps.is_none() && expected.oracle.is_none() && expected.input_account.is_none());
+        assert_eq!(decoded(1, &v1), encode(&expected));
+    }
+
+    #[test]
+    fn decodes_v2_keeping_its_step_options() {
+        let v4 = v4_step();
+        let v2 = SwapStepV2 {
+            input_mint: v4.input_mint,
+            output_mint: v4.output_mint,
+            dex: v4.dex,
+            program_id: v4.program_id,
+            expected_rate: v4.expected_rate,
+            route_data: v4.route_data,
+            slippage_bps: v4.slippage_bps,
+            account_indices: v4.account_indices,
+            split: v4.split,
+            remainder_dex: v4.remainder_dex,
+            exact_output: v4.exact_output,
+            oracle: v4.oracle,
+            liquidity: v4.liquidity,
+        };
+        let expected = SwapStep::from(v2.clone());
+        assert_eq!(expected.slippage_bps, Some(25));
+        assert!(expected.live_reserves.is_none() && expected.expected_output.is_none());
+        assert_eq!(decoded(2, &v2), encode(&expected));
+    }
+
+    #[test]
+    fn decodes_v3_and_v4_keeping_their_additions() {
+        let v4 = v4_step();
+        let v3 = SwapStepV3 {
+            input_mint: v4.input_mint,
+            output_mint: v4.output_mint,
+            dex: v4.dex,
+            program_id: v4.program_id,
+            expected_rate: v4.expected_rate,
+            route_data: v4.route_data.clone(),
+            slippage_bps: v4.slippage_bps,
+            account_indices: v4.account_indices.clone(),
+            split: v4.split.clone(),
+            remainder_dex: v4.remainder_dex,
+            exact_output: v4.exact_output,
+            oracle: v4.oracle.clone(),
+            liquidity: v4.liquidity.clone(),
+            live_reserves: v4.live_reserves.clone(),
+        };
+        let from_v3 = SwapStep::from(v3.clone());
+        assert!(from_v3.live_reserves.is_some() && from_v3.expected_output.is_none());
+        assert_eq!(decoded(3, &v3), encode(&from_v3));
+
+        let from_v4 = SwapStep::from(v4.clone());
+        assert_eq!(from_v4.expected_output, Some(4_321));
+        assert!(from_v4.input_account.is_none() && from_v4.output_account.is_none());
+        assert_eq!(decoded(4, &v4), encode(&from_v4));
+    }
+
+    #[test]
+    fn decodes_v5_as_the_current_layout() {
+        let step = SwapStep { input_account: Some(3), output_account: Some(4), ..SwapStep::from(v4_step()) };
+        assert_eq!(decoded(5, &step), encode(&step));
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_steps() {
+        let unknown = VersionedSwapStep { version: 6, data: encode(&SwapStep::from(v4_step())) };
+        assert!(unknown.decode().is_err());
+        let mut data = encode(&v4_step());
+        data.pop();
+        assert!(VersionedSwapStep { version: 4, data }.decode().is_err());
+    }
+
+    #[test]
+    fn reversing_a_route_inverts_rates_and_oracles() {
+        let mut route = cycle();
+        route[0].oracle = Some(StepOracle {
+            price_account: Pubkey::new_from_array([9; 32]),
+            inverted: false,
+            input_decimals: 9,
+            output_decimals: 6,
+        });
+        let reversed = orient_route(route.clone(), RouteDirection::Reverse).unwrap();
+
+        // The forward first leg runs last, the other way round
+        assert_eq!(reversed[1].input_mint, route[0].output_mint);
+        assert_eq!(reversed[1].expected_rate, 500);
+        assert_eq!(reversed[0].expected_rate, 1_996);
+        let oracle = reversed[1].oracle.as_ref().unwrap();
+        assert!(oracle.inverted);
+        assert_eq!((oracle.input_decimals, oracle.output_decimals), (6, 9));
+        assert!(reversed[0].oracle.is_none());
+    }
+
+    #[test]
+    fn reversing_rejects_rates_without_a_usable_inverse() {
+        let mut zero_rate = cycle();
+        zero_rate[0].expected_rate = 0;
+        assert_eq!(
+            orient_route(zero_rate, RouteDirection::Reverse).unwrap_err(),
+            ArbitrageError::InvalidRate.into()
+        );
+
+        let mut huge_rate = cycle();
+        huge_rate[1].expected_rate = 1_000_001;
+        assert_eq!(
+            orient_route(huge_rate, RouteDirection::Reverse).unwrap_err(),
+            ArbitrageError::InvalidRate.into()
+        );
+    }
+
+    #[test]
+    fn quoted_leg_outputs_carry_registry_fees_and_decode() {
+        let mints: Vec<Pubkey> = (1..=4).map(|m| Pubkey::new_from_array([m; 32])).collect();
+        let orca = Pubkey::new_from_array([8; 32]);
+        let (orca_leg, meteora_leg) = (split_leg(DexType::Orca, 6_000), split_leg(DexType::Meteora, 4_000));
+        let entry = |dex, program_id, fee_bps| DexEntry { dex, program_id, fee_bps, max_slippage_bps: 10000 };
+        let registry = DexRegistry {
+            entries: vec![
+                entry(DexType::Raydium, Pubkey::new_from_array([7; 32]), 25),
+                entry(DexType::Orca, orca, 30),
+                entry(DexType::Orca, orca_leg.program_id, 30),
+                entry(DexType::Meteora, meteora_leg.program_id, 10),
+            ],
+            bump: 255,
+        };
+        let route = vec![
+            step(mints[0], mints[1], 2_000),
+            SwapStep { program_id: orca, ..on_dex(step(mints[1], mints[2], 500), DexType::Orca) },
+            SwapStep { split: Some(vec![orca_leg, meteora_leg]), ..step(mints[2], mints[3], 1_500) },
+            on_dex(step(mints[3], mints[0], 1_000), DexType::Lifinity),
+        ];
+
+        // The split pays 60% of Orca's 30 bps and 40% of Meteora's 10 bps
+        assert_eq!(registry.step_fee_bps(&route[2]), 22);
+
+        let outputs = project_leg_outputs(&route, 1_000_000, &config(), |step| registry.step_fee_bps(step)).unwrap();
+        let decoded = Vec::<u64>::try_from_slice(&encode(&outputs)).unwrap();
+        // 2_000_000 less 25 bps, 997_500 less 30 bps and 1_491_762 less 22 bps,
+        // each rounded up; the unregistered venue takes nothing
+        assert_eq!(decoded, vec![1_995_000, 994_508, 1_488_481, 1_488_481]);
+
+        // The fee-free projection the profit floor uses is unchanged
+        assert_eq!(project_route_output(&route, 1_000_000, &config()).unwrap(), 1_500_000);
+    }
+
+    #[test]
+    fn an_empty_start_account_is_rejected_without_a_flash_loan() {
+        assert_eq!(starting_trade_amount(0, 1_000, 0).unwrap_err(), ArbitrageError::ZeroStartBalance.into());
+        assert_eq!(starting_trade_amount(500, 0, 0).unwrap_err(), ArbitrageError::ZeroStartBalance.into());
+
+        // The cap limits what is put at risk; a loan trades its own amount
+        assert_eq!(starting_trade_amount(5_000, 1_000, 0).unwrap(), 1_000);
+        assert_eq!(starting_trade_amount(0, 1_000, 2_500).unwrap(), 2_500);
+    }
+
+    #[test]
+    fn whale_amounts_stay_exact_past_the_u64_product() {
+        // 5e12 at a rate of 1e6, then 30 bps: the products exceed u64 but the results fit
+        let quoted = project_step_output(5_000_000_000_000, 1_000_000).unwrap();
+        assert_eq!(quoted, 5_000_000_000_000_000);
+        assert_eq!(after_venue_fee(quoted, 30).unwrap(), 4_985_000_000_000_000);
+        assert_eq!(calculate_profit_bps(10_000_000_000_000_000, 1_000_000_000_000).unwrap(), 100_000_000);
+        assert_eq!(calculate_bps_share(u64::MAX, 10000).unwrap(), u64::MAX);
+
+        // A quote that doesn't fit u64 fails instead of wrapping
+        assert_eq!(project_step_output(u64::MAX, 1_000_000).unwrap_err(), ArbitrageError::MathOverflow.into());
+        // profit_bps saturates rather than reverting a settled run
+        assert_eq!(calculate_profit_bps(u64::MAX, 1).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn simulated_profit_matches_a_hand_computed_three_step_route() {
+        let (a, b, c) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]), Pubkey::new_from_array([3; 32]));
+        let route = vec![step(a, b, 2_000), step(b, c, 700), step(c, a, 750)];
+        validate_route_continuity(&route, a).unwrap();
+        validate_route_cycle(&route).unwrap();
+
+        // 1_000_000 -> 2_000_000 -> 1_400_000 -> 1_050_000, a 5% gain
+        assert_eq!(project_route_output(&route, 1_000_000, &config()).unwrap(), 1_050_000);
+        assert_eq!(project_route_profit_bps(&route, 1_000_000, &config()).unwrap(), 500);
+    }
+
+    #[test]
+    fn net_profit_preview_takes_fees_and_tip_off_the_gross() {
+        let mut config = config();
+        config.fee_bps = 1_000;
+        config.strategist_bps = 2_000;
+
+        // 10% of 50_000 to the protocol, 20% of the remaining 45_000 to the strategist
+        let preview = NetProfitPreview::new(50_000, &config, 1_500, 5_000).unwrap();
+        assert_eq!(preview.fees, 5_000 + 9_000 + 5_000);
+        assert_eq!(preview.tip, 1_500);
+        assert_eq!(preview.net, preview.gross - preview.fees - preview.tip);
+        assert_eq!(preview.net, 29_500);
+
+        // Costs beyond the gross floor the net at zero
+        assert_eq!(NetProfitPreview::new(1_000, &config, 1_500, 0).unwrap().net, 0);
+    }
+
+    #[test]
+    fn a_round_trip_through_one_pool_is_a_self_sandwich() {
+        let pool = Pubkey::new_unique();
+        let raydium_accounts = |pool| {
+            [Pubkey::new_unique(), Pubkey::new_unique(), pool]
+                .map(|key| account_info(key, false, Pubkey::default(), Vec::new()))
+                .to_vec()
+        };
+        let route = cycle();
+        let same_pool = [raydium_accounts(pool), raydium_accounts(pool)];
+        assert_eq!(find_self_sandwich(&route, &same_pool), Some((0, 1)));
+
+        let other_pool = [raydium_accounts(pool), raydium_accounts(Pubkey::new_unique())];
+        assert_eq!(find_self_sandwich(&route, &other_pool), None);
+
+        // A split leg's accounts belong to its portions, so it never matches
+        let mut split_route = route.clone();
+        split_route[1].split = Some(vec![split_leg(DexType::Raydium, 10000)]);
+        assert_eq!(find_self_sandwich(&split_route, &same_pool), None);
+    }
+
+    #[test]
+    fn replaying_crafted_snapshots_prices_each_pool_deterministically() {
+        let stable = PoolStateSnapshot { reserve_in: 1_000_000_000, reserve_out: 1_000_000_000, fee_bps: 0, amp: Some(100) };
+        let constant_product = PoolStateSnapshot { reserve_in: 1_000_000, reserve_out: 2_000_000, fee_bps: 30, amp: None };
+
+        // The stable leg trades near 1:1; the second pays 2M * 996_990 / (1M + 996_990)
+        // on what its 30 bps fee leaves of 999_990
+        let outputs = replay_leg_outputs(&[stable.clone(), constant_product.clone()], 1_000_000).unwrap();
+        assert_eq!(outputs, vec![999_990, 998_492]);
+        assert_eq!(replay_leg_outputs(&[stable, constant_product], 1_000_000).unwrap(), outputs);
+
+        let drained = PoolStateSnapshot { reserve_in: 0, reserve_out: 2_000_000, fee_bps: 30, amp: None };
+        assert_eq!(snapshot_output(&drained, 1_000).unwrap_err(), ArbitrageError::InsufficientLiquidity.into());
+        let overcharged = PoolStateSnapshot { fee_bps: 10001, ..drained };
+        assert_eq!(snapshot_output(&overcharged, 1_000).unwrap_err(), ArbitrageError::InvalidFee.into());
+    }
+
+    #[test]
+    fn preflight_counts_every_positional_account_and_checks_indices() {
+        // Each Raydium leg takes an output account and its 16 pool accounts
+        let route = cycle();
+        let required = positional_account_count(&route, 34).unwrap();
+        assert_eq!(required, 2 * (1 + RAYDIUM_ACCOUNT_COUNT));
+
+        // Indexed legs take nothing positionally, but every index must exist
+        let mut indexed = cycle();
+        indexed[1].output_account = Some(0);
+        indexed[1].account_indices = Some((1..=RAYDIUM_ACCOUNT_COUNT as u8).collect());
+        assert_eq!(positional_account_count(&indexed, 17).unwrap(), 1 + RAYDIUM_ACCOUNT_COUNT);
+        assert_eq!(positional_account_count(&indexed, 16).unwrap_err(), ArbitrageError::MissingAccounts.into());
+    }
+
+    #[test]
+    fn logging_off_changes_no_result() {
+        let verbose = ProgramConfig { verbose_logs: true, ..config() };
+        let route = cycle();
+        assert_eq!(
+            project_leg_outputs(&route, 1_000_000, &config(), |_| 25).unwrap(),
+            project_leg_outputs(&route, 1_000_000, &verbose, |_| 25).unwrap()
+        );
+        assert_eq!(
+            project_route_profit_bps(&route, 1_000_000, &config()).unwrap(),
+            project_route_profit_bps(&route, 1_000_000, &verbose).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_custom_program_gets_a_delegate_and_no_signatures() {
+        let mock_program = Pubkey::new_unique();
+        let custom = SwapStep {
+            program_id: mock_program,
+            route_data: vec![3, 0xaa, 0xbb],
+            ..on_dex(cycle().remove(0), DexType::Custom)
+        };
+        let input = token_account(Pubkey::new_unique(), custom.input_mint, 1_000);
+        let output = token_account(Pubkey::new_unique(), custom.output_mint, 0);
+        let delegate = account_info(Pubkey::new_unique(), false, mock_program, Vec::new());
+        // The user signed the transaction; listing them must not hand the program that signature
+        let user = signer(Pubkey::new_unique());
+        let mut pool_config = account_info(Pubkey::new_unique(), false, mock_program, Vec::new());
+        pool_config.is_writable = false;
+        let accounts = StepAccounts { input: &input, output: &output, dex: &[] };
+
+        let instruction = create_custom_swap_instruction(&custom, &accounts, &delegate, &[user.clone(), pool_config.clone()]);
+        assert_eq!(instruction.program_id, mock_program);
+        assert_eq!(instruction.data, vec![0xaa, 0xbb]);
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys, vec![input.key(), output.key(), delegate.key(), user.key(), pool_config.key()]);
+        assert!(instruction.accounts.iter().all(|meta| !meta.is_signer));
+        let writable: Vec<bool> = instruction.accounts.iter().map(|meta| meta.is_writable).collect();
+        assert_eq!(writable, vec![true, true, false, true, false]);
+
+        // The program and its three accounts, delegate included
+        assert_eq!(dex_account_count(&custom).unwrap(), 4);
+    }
+}
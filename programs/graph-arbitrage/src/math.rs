@@ -0,0 +1,215 @@
+// 🧮 CHECKED SWAP MATH
+// programs/graph-arbitrage/src/math.rs
+//
+// Every amount, rate and bps computation goes through here so overflow and
+// division by zero surface as errors instead of panics or silent wrapping.
+
+use crate::ArbitrageError;
+
+// Which way a division that doesn't come out even is rounded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Floor,
+    Ceil, // For lower bounds like min_output, so rounding never loosens them
+}
+
+// a * b / c, with the product widened to u128 and the quotient floored
+pub fn checked_mul_div(a: u64, b: u64, c: u64) -> Result<u64, ArbitrageError> {
+    checked_mul_div_rounded(a, b, c, Rounding::Floor)
+}
+
+// a * b / c, with the product widened to u128 and the quotient rounded as asked
+pub fn checked_mul_div_rounded(a: u64, b: u64, c: u64, rounding: Rounding) -> Result<u64, ArbitrageError> {
+    if c == 0 {
+        return Err(ArbitrageError::DivisionByZero);
+    }
+    let product = (a as u128)
+        .checked_mul(b as u128)
+        .ok_or(ArbitrageError::MathOverflow)?;
+    let quotient = match rounding {
+        Rounding::Floor => product / c as u128,
+        Rounding::Ceil => product.div_ceil(c as u128),
+    };
+    u64::try_from(quotient).map_err(|_| ArbitrageError::MathOverflow)
+}
+
+// x * y = k output for amount_in against the given reserves, fee already taken off amount_in
+pub fn constant_product_output(reserve_in: u64, reserve_out: u64, amount_in: u64) -> Result<u64, ArbitrageError> {
+    let denominator = reserve_in.checked_add(amount_in).ok_or(ArbitrageError::MathOverflow)?;
+    checked_mul_div(reserve_out, amount_in, denominator)
+}
+
+// 🧮 STABLE-SWAP INVARIANT (two coins, Curve/Saber style)
+//
+// A * n^n * sum(x) + D = A * D * n^n + D^(n+1) / (n^n * prod(x)), solved for D
+// and then for the output reserve by Newton's method, as the Saber program does.
+const STABLE_SWAP_COINS: u128 = 2;
+const STABLE_SWAP_MAX_ITERATIONS: usize = 32;
+
+fn mul(a: u128, b: u128) -> Result<u128, ArbitrageError> {
+    a.checked_mul(b).ok_or(ArbitrageError::MathOverflow)
+}
+
+fn div(a: u128, b: u128) -> Result<u128, ArbitrageError> {
+    a.checked_div(b).ok_or(ArbitrageError::DivisionByZero)
+}
+
+fn compute_d(amp: u128, x: u128, y: u128) -> Result<u128, ArbitrageError> {
+    let sum = x + y;
+    if sum == 0 {
+        return Ok(0);
+    }
+    let leverage = mul(amp, STABLE_SWAP_COINS)?;
+    let mut d = sum;
+    for _ in 0..STABLE_SWAP_MAX_ITERATIONS {
+        let d_p = div(mul(div(mul(d, d)?, mul(x, STABLE_SWAP_COINS)?)?, d)?, mul(y, STABLE_SWAP_COINS)?)?;
+        let d_prev = d;
+        let numerator = mul(mul(leverage, sum)? + mul(d_p, STABLE_SWAP_COINS)?, d)?;
+        let denominator = mul(leverage - 1, d)? + mul(STABLE_SWAP_COINS + 1, d_p)?;
+        d = div(numerator, denominator)?;
+        if d.abs_diff(d_prev) <= 1 {
+            break;
+        }
+    }
+    Ok(d)
+}
+
+fn compute_y(amp: u128, x: u128, d: u128) -> Result<u128, ArbitrageError> {
+    let leverage = mul(amp, STABLE_SWAP_COINS)?;
+    let c = div(mul(div(mul(d, d)?, mul(x, STABLE_SWAP_COINS)?)?, d)?, mul(leverage, STABLE_SWAP_COINS)?)?;
+    let b = x + div(d, leverage)?;
+    let mut y = d;
+    for _ in 0..STABLE_SWAP_MAX_ITERATIONS {
+        let y_prev = y;
+        let denominator = (mul(y, 2)? + b).checked_sub(d).ok_or(ArbitrageError::MathOverflow)?;
+        y = div(mul(y, y)? + c, denominator)?;
+        if y.abs_diff(y_prev) <= 1 {
+            break;
+        }
+    }
+    Ok(y)
+}
+
+// Output for amount_in against a two-coin stable pool, before fees. Rounded
+// down by one unit so the quote never exceeds what the pool can pay.
+pub fn stable_swap_output(
+    amp: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_in: u64,
+) -> Result<u64, ArbitrageError> {
+    if amp == 0 || reserve_in == 0 || reserve_out == 0 {
+        return Err(ArbitrageError::DivisionByZero);
+    }
+    let (amp, reserve_in, reserve_out) = (amp as u128, reserve_in as u128, reserve_out as u128);
+    let d = compute_d(amp, reserve_in, reserve_out)?;
+    let new_reserve_out = compute_y(amp, reserve_in + amount_in as u128, d)?;
+    let output = reserve_out.saturating_sub(new_reserve_out).saturating_sub(1);
+    u64::try_from(output).map_err(|_| ArbitrageError::MathOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_div_widens_the_product() {
+        // u64::MAX * 3 overflows u64 but the quotient fits
+        assert_eq!(checked_mul_div(u64::MAX, 3, 6).unwrap(), u64::MAX / 2);
+        assert_eq!(checked_mul_div(1_000, 9_970, 10_000).unwrap(), 997);
+    }
+
+    #[test]
+    fn mul_div_rejects_an_oversized_quotient() {
+        assert!(matches!(checked_mul_div(u64::MAX, 2, 1), Err(ArbitrageError::MathOverflow)));
+    }
+
+    #[test]
+    fn mul_div_rejects_a_zero_divisor() {
+        assert!(matches!(checked_mul_div(1, 1, 0), Err(ArbitrageError::DivisionByZero)));
+        assert!(matches!(checked_mul_div(0, 0, 0), Err(ArbitrageError::DivisionByZero)));
+    }
+
+    #[test]
+    fn mul_div_rounds_an_uneven_quotient_as_asked() {
+        assert_eq!(checked_mul_div_rounded(10, 1, 3, Rounding::Floor).unwrap(), 3);
+        assert_eq!(checked_mul_div_rounded(10, 1, 3, Rounding::Ceil).unwrap(), 4);
+        // An even quotient comes out the same either way
+        assert_eq!(checked_mul_div_rounded(9, 1, 3, Rounding::Ceil).unwrap(), 3);
+        // (2^65 - 1) / 2 floors to u64::MAX, but rounding it up leaves u64
+        assert_eq!(checked_mul_div_rounded(1_190_112_520_884_487_201, 31, 2, Rounding::Floor).unwrap(), u64::MAX);
+        assert!(matches!(
+            checked_mul_div_rounded(1_190_112_520_884_487_201, 31, 2, Rounding::Ceil),
+            Err(ArbitrageError::MathOverflow)
+        ));
+    }
+
+    // How far D is from solving the invariant for x and y, relative to the size
+    // of its terms. The leverage is amp * n, as in compute_d (Saber's amp already carries n^(n-1)).
+    fn invariant_residual(amp: u128, x: u128, y: u128, d: u128) -> f64 {
+        let (leverage, x, y, d) = (amp as f64 * 2.0, x as f64, y as f64, d as f64);
+        let lhs = leverage * (x + y) + d;
+        let rhs = leverage * d + d * d * d / (4.0 * x * y);
+        ((lhs - rhs) / lhs).abs()
+    }
+
+    #[test]
+    fn balanced_pool_invariant_is_the_sum() {
+        for amp in [1, 100, 2_000] {
+            assert_eq!(compute_d(amp, 1_000_000_000, 1_000_000_000).unwrap(), 2_000_000_000);
+        }
+    }
+
+    #[test]
+    fn balanced_pool_trades_near_one_to_one() {
+        let output = stable_swap_output(100, 1_000_000_000, 1_000_000_000, 1_000_000).unwrap();
+        assert_eq!(output, 999_990);
+        assert!(output > constant_product_output(1_000_000_000, 1_000_000_000, 1_000_000).unwrap());
+    }
+
+    #[test]
+    fn skewed_pool_prices_the_scarce_coin_up() {
+        let balanced = stable_swap_output(100, 1_000_000_000, 1_000_000_000, 1_000_000).unwrap();
+        let into_scarce = stable_swap_output(100, 1_900_000_000, 100_000_000, 1_000_000).unwrap();
+        let out_of_scarce = stable_swap_output(100, 100_000_000, 1_900_000_000, 1_000_000).unwrap();
+        assert_eq!(into_scarce, 684_636);
+        assert_eq!(out_of_scarce, 1_453_091);
+        assert!(into_scarce < balanced && balanced < out_of_scarce);
+    }
+
+    #[test]
+    fn amp_one_sits_between_constant_product_and_a_flatter_curve() {
+        let amount_in = 100_000_000;
+        let constant_product = constant_product_output(1_000_000_000, 1_000_000_000, amount_in).unwrap();
+        let amp_one = stable_swap_output(1, 1_000_000_000, 1_000_000_000, amount_in).unwrap();
+        let amp_hundred = stable_swap_output(100, 1_000_000_000, 1_000_000_000, amount_in).unwrap();
+        assert_eq!(amp_one, 95_227_299);
+        assert!(constant_product < amp_one && amp_one < amp_hundred && amp_hundred <= amount_in);
+    }
+
+    #[test]
+    fn newton_iterations_converge_on_lopsided_pools() {
+        for (amp, x, y) in [
+            (1, 1_000_000, 1_000_000_000_000),
+            (2_000, 1_000_000, 1_000_000_000_000),
+            (2_000, 1, 1_000_000_000),
+            (100, 1_900_000_000, 100_000_000),
+        ] {
+            let d = compute_d(amp, x, y).unwrap();
+            assert!(invariant_residual(amp, x, y, d) < 1e-6, "D for {:?}", (amp, x, y));
+            // Solving back for y at the same D lands on the reserve we started from
+            let solved = compute_y(amp, x, d).unwrap();
+            assert!(solved.abs_diff(y) * 1_000_000 <= y, "y for {:?}: {}", (amp, x, y), solved);
+        }
+    }
+
+    #[test]
+    fn output_never_beats_constant_sum_on_a_balanced_pool() {
+        for amp in [1, 10, 100, 1_000, 10_000] {
+            for amount_in in [1, 1_000, 1_000_000, 500_000_000, 10_000_000_000] {
+                let output = stable_swap_output(amp, 1_000_000_000, 1_000_000_000, amount_in).unwrap();
+                assert!(output <= amount_in, "amp {} in {} out {}", amp, amount_in, output);
+            }
+        }
+    }
+}